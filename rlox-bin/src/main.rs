@@ -1,7 +1,7 @@
-use rlox::interpreter::{Interpreter, InterpreterArgs, Parser};
+use rlox_lib::runner::{InterpreterRunner, InterpreterRunnerArgs, Parser};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = InterpreterArgs::parse();
+    let args = InterpreterRunnerArgs::parse();
 
-    Ok(Interpreter::new(args).run()?)
+    Ok(InterpreterRunner::new(args).run()?)
 }
@@ -1,60 +1,164 @@
-//! Our Grammar
-//! expression     → literal
-//!                | unary
-//!                | binary
-//!                | grouping ;
+//! The syntax tree the [parser](crate::parser) builds, and the visitors that walk it.
 //!
-//! literal        → NUMBER | STRING | "true" | "false" | "nil" ;
-//! grouping       → "(" expression ")" ;
-//! unary          → ( "-" | "!" ) expression ;
-//! binary         → expression operator expression ;
-//! operator       → "==" | "!=" | "<" | "<=" | ">" | ">="
-//!                | "+"  | "-"  | "*" | "/" ;
-
-pub(crate) mod printer;
-use crate::tokens::{Token, TokenType, Value};
-use std::{fmt::Display, rc::Rc};
-
-pub(crate) trait Visitor {
+//! Every node keeps the [`Span`] of the code it was parsed from. The grammar, with the
+//! expression rules going from the loosest binding operators to the tightest:
+//!
+//! ```text
+//! program        → declaration* EOF ;
+//!
+//! declaration    → classDecl
+//!                | funDecl
+//!                | varDecl
+//!                | statement ;
+//!
+//! classDecl      → "class" IDENTIFIER ( "<" IDENTIFIER )?
+//!                  "{" function* "}" ;
+//! funDecl        → "fun" function ;
+//! function       → IDENTIFIER "(" parameters? ")" block ;
+//! parameters     → IDENTIFIER ( "," IDENTIFIER )* ;
+//! varDecl        → "var" IDENTIFIER ( "=" expression )? ";" ;
+//!
+//! statement      → exprStmt
+//!                | forStmt
+//!                | ifStmt
+//!                | printStmt
+//!                | returnStmt
+//!                | whileStmt
+//!                | block ;
+//!
+//! exprStmt       → expression ";" ;
+//! forStmt        → "for" "(" ( varDecl | exprStmt | ";" )
+//!                  expression? ";"
+//!                  expression? ")" statement ;
+//! ifStmt         → "if" "(" expression ")" statement
+//!                  ( "else" statement )? ;
+//! printStmt      → "print" expression ";" ;
+//! returnStmt     → "return" expression? ";" ;
+//! whileStmt      → "while" "(" expression ")" statement ;
+//! block          → "{" declaration* "}" ;
+//!
+//! expression     → assignment ;
+//! assignment     → ( call "." )? IDENTIFIER "=" assignment
+//!                | logic_or ;
+//! logic_or       → logic_and ( "or" logic_and )* ;
+//! logic_and      → equality ( "and" equality )* ;
+//! equality       → comparison ( ( "!=" | "==" ) comparison )* ;
+//! comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
+//! term           → factor ( ( "-" | "+" ) factor )* ;
+//! factor         → unary ( ( "/" | "*" ) unary )* ;
+//! unary          → ( "!" | "-" ) unary
+//!                | call ;
+//! call           → primary ( "(" arguments? ")" | "." IDENTIFIER )* ;
+//! arguments      → expression ( "," expression )* ;
+//! primary        → NUMBER | STRING | "true" | "false" | "nil"
+//!                | interpolation
+//!                | "(" expression ")"
+//!                | IDENTIFIER
+//!                | "this"
+//!                | "super" "." IDENTIFIER ;
+//!
+//! interpolation  → ( INTERPOLATION expression )+ INTERPOLATION_END ;
+//! ```
+
+pub mod printer;
+use crate::tokens::{Literal, Loc, Span, TokenType};
+use std::{cell::Cell, fmt::Display, rc::Rc};
+
+pub trait Visitor {
     type Output;
     fn visit_binary(&self, expr: &Bin) -> Self::Output;
     fn visit_unary(&self, expr: &Un) -> Self::Output;
     fn visit_literal(&self, expr: &Lit) -> Self::Output;
     fn visit_grouping(&self, expr: &Rc<Expr>) -> Self::Output;
+    fn visit_variable(&self, expr: &Var) -> Self::Output;
+    fn visit_assign(&self, expr: &Assign) -> Self::Output;
+    fn visit_logical(&self, expr: &Logical) -> Self::Output;
+    fn visit_call(&self, expr: &Call) -> Self::Output;
+    fn visit_get(&self, expr: &Get) -> Self::Output;
+    fn visit_set(&self, expr: &Set) -> Self::Output;
+    fn visit_this(&self, expr: &Var) -> Self::Output;
+    fn visit_super(&self, expr: &Super) -> Self::Output;
+    fn visit_interpolation(&self, expr: &Interpolation) -> Self::Output;
+}
+pub trait StmtVisitor {
+    type Output;
+    fn visit_expression_stmt(&self, expr: &Expr) -> Self::Output;
+    fn visit_print_stmt(&self, stmt: &Print) -> Self::Output;
+    fn visit_var_stmt(&self, stmt: &VarDecl) -> Self::Output;
+    fn visit_block_stmt(&self, stmts: &[Stmt]) -> Self::Output;
+    fn visit_if_stmt(&self, stmt: &If) -> Self::Output;
+    fn visit_while_stmt(&self, stmt: &While) -> Self::Output;
+    fn visit_function_stmt(&self, stmt: &Rc<Function>) -> Self::Output;
+    fn visit_return_stmt(&self, stmt: &Return) -> Self::Output;
+    fn visit_class_stmt(&self, stmt: &Class) -> Self::Output;
 }
-pub(crate) trait VisitorMut {
+pub trait VisitorMut {
     type Output;
     fn visit_binary_mut(&mut self, expr: &Bin) -> Self::Output;
     fn visit_unary_mut(&mut self, expr: &Un) -> Self::Output;
     fn visit_literal_mut(&mut self, expr: &Lit) -> Self::Output;
     fn visit_grouping_mut(&mut self, expr: &Rc<Expr>) -> Self::Output;
+    fn visit_variable_mut(&mut self, expr: &Var) -> Self::Output;
+    fn visit_assign_mut(&mut self, expr: &Assign) -> Self::Output;
+    fn visit_logical_mut(&mut self, expr: &Logical) -> Self::Output;
+    fn visit_call_mut(&mut self, expr: &Call) -> Self::Output;
+    fn visit_get_mut(&mut self, expr: &Get) -> Self::Output;
+    fn visit_set_mut(&mut self, expr: &Set) -> Self::Output;
+    fn visit_this_mut(&mut self, expr: &Var) -> Self::Output;
+    fn visit_super_mut(&mut self, expr: &Super) -> Self::Output;
+    fn visit_interpolation_mut(&mut self, expr: &Interpolation) -> Self::Output;
+}
+pub trait StmtVisitorMut {
+    type Output;
+    fn visit_expression_stmt_mut(&mut self, expr: &Expr) -> Self::Output;
+    fn visit_print_stmt_mut(&mut self, stmt: &Print) -> Self::Output;
+    fn visit_var_stmt_mut(&mut self, stmt: &VarDecl) -> Self::Output;
+    fn visit_block_stmt_mut(&mut self, stmts: &[Stmt]) -> Self::Output;
+    fn visit_if_stmt_mut(&mut self, stmt: &If) -> Self::Output;
+    fn visit_while_stmt_mut(&mut self, stmt: &While) -> Self::Output;
+    fn visit_function_stmt_mut(&mut self, stmt: &Rc<Function>) -> Self::Output;
+    fn visit_return_stmt_mut(&mut self, stmt: &Return) -> Self::Output;
+    fn visit_class_stmt_mut(&mut self, stmt: &Class) -> Self::Output;
 }
 
 #[derive(Debug)]
-pub(crate) enum Lit {
+pub enum Lit {
     True,
     False,
     Nil,
-    Num(Value),
-    Str(Value),
+    Num(Literal),
+    Str(Literal),
 }
 
 #[derive(Debug)]
-pub(crate) enum Un {
-    Minus(Rc<Expr>),
-    Bang(Rc<Expr>),
+pub enum Un {
+    Minus(Rc<Expr>, Loc, Span),
+    Bang(Rc<Expr>, Loc, Span),
 }
 
 impl Un {
-    fn inner(&self) -> &Rc<Expr> {
+    pub fn inner(&self) -> &Rc<Expr> {
         match self {
-            Un::Minus(x) | Un::Bang(x) => x,
+            Un::Minus(x, ..) | Un::Bang(x, ..) => x,
+        }
+    }
+
+    /// Location of the operator token
+    pub fn loc(&self) -> Loc {
+        match self {
+            Un::Minus(_, loc, _) | Un::Bang(_, loc, _) => *loc,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Un::Minus(.., span) | Un::Bang(.., span) => *span,
         }
     }
 }
 
 #[derive(Debug)]
-pub(crate) enum Ops {
+pub enum Ops {
     Minus,
     Plus,
     BangEqual,
@@ -67,89 +171,371 @@ pub(crate) enum Ops {
     LessEqual,
 }
 
-impl From<&TokenType> for Ops {
-    fn from(value: &TokenType) -> Self {
-        match value {
-            crate::tokens::TokenType::LeftParen
-            | crate::tokens::TokenType::RightParen
-            | crate::tokens::TokenType::LeftBrace
-            | crate::tokens::TokenType::RightBrace
-            | crate::tokens::TokenType::Comma
-            | crate::tokens::TokenType::Dot
-            | crate::tokens::TokenType::Bang
-            | crate::tokens::TokenType::Equal
-            | crate::tokens::TokenType::Semicolon
-            | crate::tokens::TokenType::Eof
-            | crate::tokens::TokenType::Identifier
-            | crate::tokens::TokenType::String
-            | crate::tokens::TokenType::Number
-            | crate::tokens::TokenType::And
-            | crate::tokens::TokenType::Class
-            | crate::tokens::TokenType::Else
-            | crate::tokens::TokenType::False
-            | crate::tokens::TokenType::Fun
-            | crate::tokens::TokenType::For
-            | crate::tokens::TokenType::If
-            | crate::tokens::TokenType::Nil
-            | crate::tokens::TokenType::Or
-            | crate::tokens::TokenType::Print
-            | crate::tokens::TokenType::Return
-            | crate::tokens::TokenType::Super
-            | crate::tokens::TokenType::This
-            | crate::tokens::TokenType::True
-            | crate::tokens::TokenType::Var
-            | crate::tokens::TokenType::While => panic!("invalid operator"),
-            crate::tokens::TokenType::Minus => Self::Minus,
-            crate::tokens::TokenType::Plus => Self::Plus,
-            crate::tokens::TokenType::Slash => Self::Slash,
-            crate::tokens::TokenType::Star => Self::Star,
-            crate::tokens::TokenType::BangEqual => Self::BangEqual,
-            crate::tokens::TokenType::EqualEqual => Self::EqualEqual,
-            crate::tokens::TokenType::Greater => Self::Greater,
-            crate::tokens::TokenType::GreaterEqual => Self::GreaterEqual,
-            crate::tokens::TokenType::Less => Self::Less,
-            crate::tokens::TokenType::LessEqual => Self::LessEqual,
+impl TryFrom<&TokenType> for Ops {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &TokenType) -> Result<Self, Self::Error> {
+        Ok(match value {
+            TokenType::Minus => Self::Minus,
+            TokenType::Plus => Self::Plus,
+            TokenType::Slash => Self::Slash,
+            TokenType::Star => Self::Star,
+            TokenType::BangEqual => Self::BangEqual,
+            TokenType::EqualEqual => Self::EqualEqual,
+            TokenType::Greater => Self::Greater,
+            TokenType::GreaterEqual => Self::GreaterEqual,
+            TokenType::Less => Self::Less,
+            TokenType::LessEqual => Self::LessEqual,
+            other => anyhow::bail!("{other} is not a binary operator"),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Bin {
+    pub left: Rc<Expr>,
+    pub op: Ops,
+    pub right: Rc<Expr>,
+    /// Location of the operator token
+    pub loc: Loc,
+    pub span: Span,
+}
+
+/// A read of the variable `name`
+#[derive(Debug)]
+pub struct Var {
+    pub name: Rc<str>,
+    pub loc: Loc,
+    /// How many scopes up the variable was declared, `None` for globals.
+    /// Filled in by the resolver
+    pub(crate) depth: Cell<Option<usize>>,
+    pub span: Span,
+}
+
+impl Var {
+    pub fn new(name: Rc<str>, loc: Loc, span: Span) -> Self {
+        Self {
+            name,
+            loc,
+            depth: Cell::new(None),
+            span,
         }
     }
+
+    /// How many scopes up the resolver found the variable, `None` for globals
+    pub fn depth(&self) -> Option<usize> {
+        self.depth.get()
+    }
+}
+
+#[derive(Debug)]
+pub struct Assign {
+    pub name: Rc<str>,
+    pub value: Rc<Expr>,
+    /// Location of the assigned variable
+    pub loc: Loc,
+    /// See [`Var::depth`]
+    pub(crate) depth: Cell<Option<usize>>,
+    pub span: Span,
+}
+
+impl Assign {
+    /// How many scopes up the resolver found the variable, `None` for globals
+    pub fn depth(&self) -> Option<usize> {
+        self.depth.get()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogicalOp {
+    And,
+    Or,
+}
+
+/// `and`/`or`, kept apart from [`Bin`] since the right side may never be evaluated
+#[derive(Debug)]
+pub struct Logical {
+    pub left: Rc<Expr>,
+    pub op: LogicalOp,
+    pub right: Rc<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Call {
+    pub callee: Rc<Expr>,
+    pub args: Vec<Expr>,
+    /// Location of the closing paren, where call errors are reported
+    pub loc: Loc,
+    pub span: Span,
+}
+
+/// A property read, `object.name`
+#[derive(Debug)]
+pub struct Get {
+    pub object: Rc<Expr>,
+    pub name: Rc<str>,
+    /// Location of the property name
+    pub loc: Loc,
+    pub span: Span,
 }
 
+/// A property write, `object.name = value`
 #[derive(Debug)]
-pub(crate) struct Bin {
-    pub(crate) left: Rc<Expr>,
-    pub(crate) op: Ops,
-    pub(crate) right: Rc<Expr>,
+pub struct Set {
+    pub object: Rc<Expr>,
+    pub name: Rc<str>,
+    pub value: Rc<Expr>,
+    /// Location of the property name
+    pub loc: Loc,
+    pub span: Span,
 }
 
+/// `super.method`
 #[derive(Debug)]
-pub(crate) enum Expr {
-    Literal(Lit),
+pub struct Super {
+    pub method: Rc<str>,
+    /// Location of the `super` keyword
+    pub loc: Loc,
+    /// How many scopes up `super` was bound, `this` is always one closer
+    pub(crate) depth: Cell<Option<usize>>,
+    pub span: Span,
+}
+
+impl Super {
+    /// How many scopes up the resolver bound `super`, `this` is always one closer
+    pub fn depth(&self) -> Option<usize> {
+        self.depth.get()
+    }
+}
+
+/// `"a ${b} c"`, evaluated by stringifying every part and concatenating them
+#[derive(Debug)]
+pub struct Interpolation {
+    /// The string segments and embedded expressions in source order,
+    /// empty segments are left out
+    pub parts: Vec<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum Expr {
+    Literal(Lit, Span),
     Unary(Un),
     Binary(Bin),
-    Grouping(Rc<Expr>),
+    /// The span includes the parentheses
+    Grouping(Rc<Expr>, Span),
+    Variable(Var),
+    Assign(Assign),
+    Logical(Logical),
+    Call(Call),
+    Get(Get),
+    Set(Set),
+    /// `this`, looked up like any other variable
+    This(Var),
+    Super(Super),
+    Interpolation(Interpolation),
 }
 
 impl Expr {
-    pub(crate) fn accept<T>(&self, visitor: &T) -> T::Output
+    /// The full extent of the expression in the source
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Literal(_, span) | Expr::Grouping(_, span) => *span,
+            Expr::Unary(un) => un.span(),
+            Expr::Binary(bin) => bin.span,
+            Expr::Variable(var) | Expr::This(var) => var.span,
+            Expr::Assign(assign) => assign.span,
+            Expr::Logical(logical) => logical.span,
+            Expr::Call(call) => call.span,
+            Expr::Get(get) => get.span,
+            Expr::Set(set) => set.span,
+            Expr::Super(sup) => sup.span,
+            Expr::Interpolation(interp) => interp.span,
+        }
+    }
+
+    pub fn accept<T>(&self, visitor: &T) -> T::Output
     where
         T: Visitor,
     {
         match self {
-            Expr::Literal(lit) => visitor.visit_literal(lit),
+            Expr::Literal(lit, _) => visitor.visit_literal(lit),
             Expr::Unary(un) => visitor.visit_unary(un),
             Expr::Binary(bin) => visitor.visit_binary(bin),
-            Expr::Grouping(grp) => visitor.visit_grouping(grp),
+            Expr::Grouping(grp, _) => visitor.visit_grouping(grp),
+            Expr::Variable(var) => visitor.visit_variable(var),
+            Expr::Assign(assign) => visitor.visit_assign(assign),
+            Expr::Logical(logical) => visitor.visit_logical(logical),
+            Expr::Call(call) => visitor.visit_call(call),
+            Expr::Get(get) => visitor.visit_get(get),
+            Expr::Set(set) => visitor.visit_set(set),
+            Expr::This(this) => visitor.visit_this(this),
+            Expr::Super(sup) => visitor.visit_super(sup),
+            Expr::Interpolation(interp) => visitor.visit_interpolation(interp),
         }
     }
 
-    pub(crate) fn accept_mut<T>(&self, visitor: &mut T) -> T::Output
+    pub fn accept_mut<T>(&self, visitor: &mut T) -> T::Output
     where
         T: VisitorMut,
     {
         match self {
-            Expr::Literal(lit) => visitor.visit_literal_mut(lit),
+            Expr::Literal(lit, _) => visitor.visit_literal_mut(lit),
             Expr::Unary(un) => visitor.visit_unary_mut(un),
             Expr::Binary(bin) => visitor.visit_binary_mut(bin),
-            Expr::Grouping(grp) => visitor.visit_grouping_mut(grp),
+            Expr::Grouping(grp, _) => visitor.visit_grouping_mut(grp),
+            Expr::Variable(var) => visitor.visit_variable_mut(var),
+            Expr::Assign(assign) => visitor.visit_assign_mut(assign),
+            Expr::Logical(logical) => visitor.visit_logical_mut(logical),
+            Expr::Call(call) => visitor.visit_call_mut(call),
+            Expr::Get(get) => visitor.visit_get_mut(get),
+            Expr::Set(set) => visitor.visit_set_mut(set),
+            Expr::This(this) => visitor.visit_this_mut(this),
+            Expr::Super(sup) => visitor.visit_super_mut(sup),
+            Expr::Interpolation(interp) => visitor.visit_interpolation_mut(interp),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct VarDecl {
+    pub name: Rc<str>,
+    pub initializer: Option<Expr>,
+    /// Location of the declared name
+    pub loc: Loc,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct If {
+    pub condition: Expr,
+    pub then_branch: Rc<Stmt>,
+    pub else_branch: Option<Rc<Stmt>>,
+    pub span: Span,
+}
+
+/// Also what `for` loops are desugared into
+#[derive(Debug)]
+pub struct While {
+    pub condition: Expr,
+    pub body: Rc<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Param {
+    pub name: Rc<str>,
+    /// Location of the parameter name
+    pub loc: Loc,
+}
+
+/// A function declaration, shared with every closure created from it
+#[derive(Debug)]
+pub struct Function {
+    pub name: Rc<str>,
+    pub params: Vec<Param>,
+    pub body: Vec<Stmt>,
+    /// Location of the function name
+    pub loc: Loc,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Print {
+    pub expr: Expr,
+    /// Location of the `print` keyword
+    pub loc: Loc,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Return {
+    pub value: Option<Expr>,
+    /// Location of the `return` keyword
+    pub loc: Loc,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: Rc<str>,
+    /// Location of the class name
+    pub loc: Loc,
+    pub superclass: Option<Var>,
+    pub methods: Vec<Rc<Function>>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum Stmt {
+    /// The span includes the closing semicolon
+    Expression(Expr, Span),
+    Print(Print),
+    Var(VarDecl),
+    Block(Vec<Stmt>, Span),
+    If(If),
+    While(While),
+    Function(Rc<Function>),
+    Return(Return),
+    Class(Class),
+}
+
+impl Stmt {
+    /// The full extent of the statement in the source
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Expression(_, span) | Stmt::Block(_, span) => *span,
+            Stmt::Print(stmt) => stmt.span,
+            Stmt::Var(decl) => decl.span,
+            Stmt::If(stmt) => stmt.span,
+            Stmt::While(stmt) => stmt.span,
+            Stmt::Function(stmt) => stmt.span,
+            Stmt::Return(stmt) => stmt.span,
+            Stmt::Class(stmt) => stmt.span,
+        }
+    }
+
+    pub fn accept<T>(&self, visitor: &T) -> T::Output
+    where
+        T: StmtVisitor,
+    {
+        match self {
+            Stmt::Expression(expr, _) => visitor.visit_expression_stmt(expr),
+            Stmt::Print(stmt) => visitor.visit_print_stmt(stmt),
+            Stmt::Var(decl) => visitor.visit_var_stmt(decl),
+            Stmt::Block(stmts, _) => visitor.visit_block_stmt(stmts),
+            Stmt::If(stmt) => visitor.visit_if_stmt(stmt),
+            Stmt::While(stmt) => visitor.visit_while_stmt(stmt),
+            Stmt::Function(stmt) => visitor.visit_function_stmt(stmt),
+            Stmt::Return(stmt) => visitor.visit_return_stmt(stmt),
+            Stmt::Class(stmt) => visitor.visit_class_stmt(stmt),
+        }
+    }
+
+    pub fn accept_mut<T>(&self, visitor: &mut T) -> T::Output
+    where
+        T: StmtVisitorMut,
+    {
+        match self {
+            Stmt::Expression(expr, _) => visitor.visit_expression_stmt_mut(expr),
+            Stmt::Print(stmt) => visitor.visit_print_stmt_mut(stmt),
+            Stmt::Var(decl) => visitor.visit_var_stmt_mut(decl),
+            Stmt::Block(stmts, _) => visitor.visit_block_stmt_mut(stmts),
+            Stmt::If(stmt) => visitor.visit_if_stmt_mut(stmt),
+            Stmt::While(stmt) => visitor.visit_while_stmt_mut(stmt),
+            Stmt::Function(stmt) => visitor.visit_function_stmt_mut(stmt),
+            Stmt::Return(stmt) => visitor.visit_return_stmt_mut(stmt),
+            Stmt::Class(stmt) => visitor.visit_class_stmt_mut(stmt),
+        }
+    }
+}
+
+impl Display for LogicalOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogicalOp::And => write!(f, "and"),
+            LogicalOp::Or => write!(f, "or"),
         }
     }
 }
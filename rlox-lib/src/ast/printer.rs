@@ -1,7 +1,27 @@
-use super::Visitor;
+use std::rc::Rc;
 
+use super::{Class, Expr, Function, If, Print, Return, Stmt, StmtVisitor, VarDecl, Visitor, While};
+
+/// Prints expressions and statements as s-expressions, e.g. `( print ( + 1 2 ) )`
 #[derive(Default)]
-pub(crate) struct AstPrinter;
+pub struct AstPrinter;
+
+impl AstPrinter {
+    /// Prints `stmts` one per line
+    pub fn print(&self, stmts: &[Stmt]) -> String {
+        stmts.iter().map(|stmt| stmt.accept(self) + "\n").collect()
+    }
+
+    fn parenthesize(&self, head: &str, stmts: &[Stmt]) -> String {
+        let mut res = format!("( {head}");
+        for stmt in stmts {
+            res.push(' ');
+            res.push_str(&stmt.accept(self));
+        }
+        res.push_str(" )");
+        res
+    }
+}
 
 impl Visitor for AstPrinter {
     type Output = String;
@@ -17,28 +37,158 @@ impl Visitor for AstPrinter {
 
     fn visit_unary(&self, expr: &super::Un) -> Self::Output {
         let sign = match expr {
-            crate::ast::Un::Minus(_) => "-",
-            crate::ast::Un::Bang(_) => "!",
+            crate::ast::Un::Minus(..) => "-",
+            crate::ast::Un::Bang(..) => "!",
         };
         format!("( {} {} )", sign, expr.inner().accept(self))
     }
 
     fn visit_literal(&self, expr: &super::Lit) -> Self::Output {
-        format!(
-            "{}",
-            match expr {
-                super::Lit::True => format!("true"),
-                super::Lit::False => format!("false"),
-                super::Lit::Nil => format!("nil"),
-                super::Lit::Num(num) => num.to_string(),
-                super::Lit::Str(s) => s.to_string(),
-            }
-        )
+        match expr {
+            super::Lit::True => "true".to_string(),
+            super::Lit::False => "false".to_string(),
+            super::Lit::Nil => "nil".to_string(),
+            super::Lit::Num(num) => num.to_string(),
+            super::Lit::Str(s) => s.to_string(),
+        }
     }
 
     fn visit_grouping(&self, expr: &std::rc::Rc<super::Expr>) -> Self::Output {
         format!("( group {} )", expr.accept(self))
     }
+
+    fn visit_variable(&self, expr: &super::Var) -> Self::Output {
+        expr.name.to_string()
+    }
+
+    fn visit_assign(&self, expr: &super::Assign) -> Self::Output {
+        format!("( = {} {} )", expr.name, expr.value.accept(self))
+    }
+
+    fn visit_call(&self, expr: &super::Call) -> Self::Output {
+        let mut res = format!("( call {}", expr.callee.accept(self));
+        for arg in &expr.args {
+            res.push(' ');
+            res.push_str(&arg.accept(self));
+        }
+        res.push_str(" )");
+        res
+    }
+
+    fn visit_get(&self, expr: &super::Get) -> Self::Output {
+        format!("( . {} {} )", expr.object.accept(self), expr.name)
+    }
+
+    fn visit_set(&self, expr: &super::Set) -> Self::Output {
+        format!(
+            "( = ( . {} {} ) {} )",
+            expr.object.accept(self),
+            expr.name,
+            expr.value.accept(self)
+        )
+    }
+
+    fn visit_this(&self, _expr: &super::Var) -> Self::Output {
+        "this".to_string()
+    }
+
+    fn visit_super(&self, expr: &super::Super) -> Self::Output {
+        format!("( . super {} )", expr.method)
+    }
+
+    fn visit_interpolation(&self, expr: &super::Interpolation) -> Self::Output {
+        let mut res = "( interp".to_string();
+        for part in &expr.parts {
+            res.push(' ');
+            res.push_str(&part.accept(self));
+        }
+        res.push_str(" )");
+        res
+    }
+
+    fn visit_logical(&self, expr: &super::Logical) -> Self::Output {
+        format!(
+            "( {} {} {} )",
+            expr.op,
+            expr.left.accept(self),
+            expr.right.accept(self)
+        )
+    }
+}
+
+impl StmtVisitor for AstPrinter {
+    type Output = String;
+
+    fn visit_expression_stmt(&self, expr: &Expr) -> Self::Output {
+        format!("( ; {} )", expr.accept(self))
+    }
+
+    fn visit_print_stmt(&self, stmt: &Print) -> Self::Output {
+        format!("( print {} )", stmt.expr.accept(self))
+    }
+
+    fn visit_var_stmt(&self, stmt: &VarDecl) -> Self::Output {
+        match &stmt.initializer {
+            Some(init) => format!("( var {} {} )", stmt.name, init.accept(self)),
+            None => format!("( var {} )", stmt.name),
+        }
+    }
+
+    fn visit_block_stmt(&self, stmts: &[Stmt]) -> Self::Output {
+        self.parenthesize("block", stmts)
+    }
+
+    fn visit_if_stmt(&self, stmt: &If) -> Self::Output {
+        let mut res = format!(
+            "( if {} {}",
+            stmt.condition.accept(self),
+            stmt.then_branch.accept(self)
+        );
+        if let Some(else_branch) = &stmt.else_branch {
+            res.push(' ');
+            res.push_str(&else_branch.accept(self));
+        }
+        res.push_str(" )");
+        res
+    }
+
+    fn visit_while_stmt(&self, stmt: &While) -> Self::Output {
+        format!(
+            "( while {} {} )",
+            stmt.condition.accept(self),
+            stmt.body.accept(self)
+        )
+    }
+
+    fn visit_function_stmt(&self, stmt: &Rc<Function>) -> Self::Output {
+        let params: String = stmt
+            .params
+            .iter()
+            .map(|param| format!("{} ", param.name))
+            .collect();
+        let head = format!("fun {} ( {params})", stmt.name);
+        self.parenthesize(&head, &stmt.body)
+    }
+
+    fn visit_return_stmt(&self, stmt: &Return) -> Self::Output {
+        match &stmt.value {
+            Some(value) => format!("( return {} )", value.accept(self)),
+            None => "( return )".to_string(),
+        }
+    }
+
+    fn visit_class_stmt(&self, stmt: &Class) -> Self::Output {
+        let mut res = format!("( class {}", stmt.name);
+        if let Some(superclass) = &stmt.superclass {
+            res.push_str(&format!(" < {}", superclass.name));
+        }
+        for method in &stmt.methods {
+            res.push(' ');
+            res.push_str(&self.visit_function_stmt(method));
+        }
+        res.push_str(" )");
+        res
+    }
 }
 
 #[cfg(test)]
@@ -46,17 +196,45 @@ mod test {
     use super::*;
     use crate::{
         ast::{Bin, Expr, Lit, Ops, Un},
-        tokens::Value,
+        tokens::{Literal, Loc, Span},
     };
 
     #[test]
     fn test_simpl_expr_visit() {
         let expr = Expr::Binary(Bin {
-            left: Expr::Unary(Un::Minus(Expr::Literal(Lit::Num(Value::Num(123.0))).into())).into(),
+            left: Expr::Unary(Un::Minus(
+                Expr::Literal(Lit::Num(Literal::Num(123.0)), Span::default()).into(),
+                Loc::default(),
+                Span::default(),
+            ))
+            .into(),
             op: Ops::Star,
-            right: Expr::Grouping(Expr::Literal(Lit::Num(Value::Num(45.67))).into()).into(),
+            right: Expr::Grouping(
+                Expr::Literal(Lit::Num(Literal::Num(45.67)), Span::default()).into(),
+                Span::default(),
+            )
+            .into(),
+            loc: Loc::default(),
+            span: Span::default(),
         });
         let res = expr.accept(&AstPrinter);
         assert_eq!(res, "( * ( - 123 ) ( group 45.67 ) )")
     }
+
+    #[test]
+    fn test_print_stmts() {
+        let code = "var a = 1;\nfun f(x, y) { if (x) return y; else print \"no\"; }\nclass B < A { m() { while (true) {} } }\n";
+        let tokens = crate::scanner::Scanner::new(code).run();
+        let stmts = crate::parser::Parser::new(tokens)
+            .parse_program()
+            .into_result()
+            .unwrap();
+        let expected = [
+            "( var a 1 )",
+            "( fun f ( x y ) ( if x ( return y ) ( print \"no\" ) ) )",
+            "( class B < A ( fun m ( ) ( while true ( block ) ) ) )",
+            "",
+        ];
+        assert_eq!(AstPrinter.print(&stmts), expected.join("\n"));
+    }
 }
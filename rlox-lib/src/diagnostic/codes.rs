@@ -0,0 +1,481 @@
+//! The code of every diagnostic we emit, and the longer explanation behind each.
+//!
+//! Codes are grouped by the stage reporting them: `E00xx` for the scanner, `E01xx` for the
+//! parser, `E02xx` for the resolver and `E03xx` for runtime errors. Once released a code keeps
+//! its meaning, however the message attached to it is reworded
+
+use super::Code;
+
+/// Declares a constant for every code, plus [`ALL`] listing them so none can be forgotten
+macro_rules! codes {
+    ($($name:ident = $number:literal,)*) => {
+        $(pub const $name: Code = Code($number);)*
+
+        /// Every code we emit, in the order they are declared
+        pub const ALL: &[Code] = &[$($name),*];
+    };
+}
+
+codes! {
+    UNTERMINATED_STRING = 1,
+    UNTERMINATED_COMMENT = 2,
+    UNEXPECTED_CHARACTER = 3,
+    INVALID_ESCAPE = 4,
+    MALFORMED_NUMBER = 5,
+
+    EXPECTED_EXPRESSION = 101,
+    EXPECTED_RIGHT_PAREN = 102,
+    EXPECTED_LEFT_PAREN = 103,
+    EXPECTED_LEFT_BRACE = 104,
+    EXPECTED_RIGHT_BRACE = 105,
+    EXPECTED_SEMICOLON = 106,
+    EXPECTED_NAME = 107,
+    EXPECTED_DOT = 108,
+    INVALID_ASSIGNMENT = 109,
+    TOO_MANY_ARGUMENTS = 110,
+    THIS_OUTSIDE_CLASS = 111,
+    INVALID_SUPER = 112,
+    SELF_INHERITANCE = 113,
+    TOO_DEEPLY_NESTED = 114,
+    EXPECTED_END = 115,
+
+    OWN_INITIALIZER = 201,
+    DUPLICATE_VARIABLE = 202,
+    TOP_LEVEL_RETURN = 203,
+    RETURN_FROM_INITIALIZER = 204,
+
+    WRONG_OPERAND_TYPE = 301,
+    UNDEFINED_VARIABLE = 302,
+    NOT_CALLABLE = 303,
+    ARITY_MISMATCH = 304,
+    NOT_AN_INSTANCE = 305,
+    UNDEFINED_PROPERTY = 306,
+    SUPERCLASS_NOT_A_CLASS = 307,
+    STACK_OVERFLOW = 308,
+    OUTPUT_FAILED = 309,
+}
+
+/// What `rlox --explain` prints about a code
+#[derive(Debug)]
+pub struct Explanation {
+    pub code: Code,
+    /// A few words naming the problem
+    pub title: &'static str,
+    /// What went wrong and how to fix it, with an example
+    pub description: &'static str,
+}
+
+/// The explanation for `code`, if it is one we emit
+pub fn explain(code: Code) -> Option<&'static Explanation> {
+    CATALOG.iter().find(|explanation| explanation.code == code)
+}
+
+const CATALOG: &[Explanation] = &[
+    Explanation {
+        code: UNTERMINATED_STRING,
+        title: "unterminated string",
+        description: "\
+A string literal was opened with `\"` but the file ended before the closing `\"`.
+Strings may span several lines, so the missing quote can be far above where
+the file ends.
+
+    print \"hello;
+
+Close the string: `print \"hello\";`",
+    },
+    Explanation {
+        code: UNTERMINATED_COMMENT,
+        title: "unterminated block comment",
+        description: "\
+A `/*` comment was never closed. Block comments nest, so every `/*` inside
+one needs its own `*/` as well.
+
+    /* outer /* inner */
+    print 1;
+
+Add the missing `*/`.",
+    },
+    Explanation {
+        code: UNEXPECTED_CHARACTER,
+        title: "unexpected character",
+        description: "\
+The source contains a character that is not part of any lox token, outside
+of a string or comment.
+
+    var a = 1 @ 2;
+
+Remove the character, or move it into a string.",
+    },
+    Explanation {
+        code: INVALID_ESCAPE,
+        title: "invalid escape sequence",
+        description: "\
+A backslash in a string is followed by something that is not a known escape.
+The escapes are `\\n`, `\\t`, `\\r`, `\\\"`, `\\\\`, `\\$` and `\\u{...}` with 1 to 6 hex
+digits naming a unicode scalar value.
+
+    print \"C:\\temp\\q\";
+
+Write `\\\\` for a literal backslash: `print \"C:\\\\temp\\\\q\";`",
+    },
+    Explanation {
+        code: MALFORMED_NUMBER,
+        title: "malformed number literal",
+        description: "\
+A number literal is not written correctly. Numbers may be decimal with an
+optional fraction and exponent (`1.5e-3`), hex (`0xFF`) or binary (`0b1010`),
+and `_` may separate digits (`1_000`).
+
+    var mask = 0x;
+    var big = 1e;
+    var odd = 1__0;
+
+Add the missing digits, and only put single `_` between digits.",
+    },
+    Explanation {
+        code: EXPECTED_EXPRESSION,
+        title: "expected an expression",
+        description: "\
+The parser needed a value here, like a literal, a variable or a call, but
+found something else.
+
+    var a = ;
+    print 1 + ;
+
+Finish the expression: `var a = 1;`",
+    },
+    Explanation {
+        code: EXPECTED_RIGHT_PAREN,
+        title: "expected ')'",
+        description: "\
+A parenthesis was opened, for a grouping, a call, a parameter list or the
+head of an `if`, `while` or `for`, and never closed.
+
+    print (1 + 2;
+    if (a == 1 print a;
+
+Add the closing `)`: `print (1 + 2);`",
+    },
+    Explanation {
+        code: EXPECTED_LEFT_PAREN,
+        title: "expected '('",
+        description: "\
+`if`, `while` and `for` need their condition in parentheses, and functions
+need a parameter list, even an empty one.
+
+    while true print 1;
+    fun f { }
+
+Add the parentheses: `while (true) print 1;`",
+    },
+    Explanation {
+        code: EXPECTED_LEFT_BRACE,
+        title: "expected '{'",
+        description: "\
+Function and class bodies must be blocks in braces.
+
+    fun f() print 1;
+
+Wrap the body in braces: `fun f() { print 1; }`",
+    },
+    Explanation {
+        code: EXPECTED_RIGHT_BRACE,
+        title: "expected '}'",
+        description: "\
+A block, class body or string interpolation was opened with `{` and never
+closed.
+
+    { print 1;
+    print \"${a b}\";
+
+Close it with `}`. Inside `${...}` only a single expression is allowed.",
+    },
+    Explanation {
+        code: EXPECTED_SEMICOLON,
+        title: "expected ';'",
+        description: "\
+Statements end with a semicolon, and a `for` loop separates its clauses with
+them.
+
+    print 1
+    var a = 2
+
+Add the semicolons: `print 1;`",
+    },
+    Explanation {
+        code: EXPECTED_NAME,
+        title: "expected a name",
+        description: "\
+A declaration or property access needs an identifier, but found a keyword,
+literal or symbol.
+
+    var 1 = 2;
+    fun (a) { }
+    print a.1;
+
+Use a name made of letters, digits and `_` that does not start with a digit.",
+    },
+    Explanation {
+        code: EXPECTED_DOT,
+        title: "expected '.' after 'super'",
+        description: "\
+`super` can only be used to look up a method of the superclass.
+
+    class B < A { f() { return super; } }
+
+Name the method: `return super.f();`",
+    },
+    Explanation {
+        code: INVALID_ASSIGNMENT,
+        title: "invalid assignment target",
+        description: "\
+Only variables and properties can be assigned to.
+
+    1 = 2;
+    a + b = c;
+    f() = 3;
+
+Assign to a variable or a field instead: `a = 2;`, `f().x = 3;`",
+    },
+    Explanation {
+        code: TOO_MANY_ARGUMENTS,
+        title: "too many arguments or parameters",
+        description: "\
+A call may pass at most 255 arguments, and a function may declare at most
+255 parameters.
+
+    fun f(a1, a2, /* ... */ a256) { }
+
+Group related values into an instance of a class and pass that instead.",
+    },
+    Explanation {
+        code: THIS_OUTSIDE_CLASS,
+        title: "'this' outside of a class",
+        description: "\
+`this` refers to the instance a method was called on, so it only exists
+inside methods.
+
+    fun f() { print this; }
+
+Move the function into a class, or pass the instance as a parameter.",
+    },
+    Explanation {
+        code: INVALID_SUPER,
+        title: "invalid use of 'super'",
+        description: "\
+`super` is only available in methods of a class that has a superclass.
+
+    class A { f() { super.f(); } }
+
+Declare a superclass with `class A < Base { ... }`, or call the method on
+`this` instead.",
+    },
+    Explanation {
+        code: SELF_INHERITANCE,
+        title: "class inherits from itself",
+        description: "\
+A class can't be its own superclass.
+
+    class A < A { }
+
+Inherit from a different class, or drop the `< A`.",
+    },
+    Explanation {
+        code: TOO_DEEPLY_NESTED,
+        title: "code nested too deeply",
+        description: "\
+Expressions and statements may nest at most 20000 levels deep, counting every
+parenthesis, operator, block and branch the code is inside of. Every link of a
+chain like `a + b + c` or `a.b().c` counts as a level as well.
+
+    print 1 + 1 + 1 + /* ... thirty thousand terms long ... */ 1;
+
+Code written by hand rarely comes close, this is mostly hit by generated code.
+Pull inner parts out into variables or functions.",
+    },
+    Explanation {
+        code: EXPECTED_END,
+        title: "expected the end of the expression",
+        description: "\
+Something follows an expression that was meant to stand on its own, like a
+value typed into the repl to see what it evaluates to.
+
+    1 + 2 3
+
+Remove what follows the expression, or join the two with an operator.",
+    },
+    Explanation {
+        code: OWN_INITIALIZER,
+        title: "local variable read in its own initializer",
+        description: "\
+A local variable can't be used in the expression that gives it its first
+value, since it doesn't have one yet.
+
+    var a = 1;
+    { var a = a + 1; }
+
+Use a different name for the inner variable: `{ var b = a + 1; }`",
+    },
+    Explanation {
+        code: DUPLICATE_VARIABLE,
+        title: "variable declared twice in one scope",
+        description: "\
+A block or function declared the same name twice. Only global variables
+may be redeclared.
+
+    fun f(a) { var a = 1; }
+    { var b; var b; }
+
+Rename one of them, or assign to the existing variable instead.",
+    },
+    Explanation {
+        code: TOP_LEVEL_RETURN,
+        title: "return outside of a function",
+        description: "\
+`return` hands a value back to the caller, so it can only be used inside a
+function or method.
+
+    return 1;
+
+Remove the `return`, or move the code into a function.",
+    },
+    Explanation {
+        code: RETURN_FROM_INITIALIZER,
+        title: "value returned from an initializer",
+        description: "\
+`init` always returns the new instance, so it can't return anything else.
+A bare `return;` to leave early is fine.
+
+    class A { init() { return 1; } }
+
+Drop the value: `return;`",
+    },
+    Explanation {
+        code: WRONG_OPERAND_TYPE,
+        title: "operand of the wrong type",
+        description: "\
+An operator was applied to values it doesn't support. Arithmetic and
+comparisons need numbers, and `+` needs either two numbers or two strings.
+
+    print \"a\" + 1;
+    print -\"b\";
+
+Convert the values first, or build the string with interpolation:
+`print \"a${1}\";`",
+    },
+    Explanation {
+        code: UNDEFINED_VARIABLE,
+        title: "undefined variable",
+        description: "\
+A variable was read or assigned before it was declared.
+
+    print a;
+    b = 1;
+
+Declare it first with `var`: `var b = 1;`",
+    },
+    Explanation {
+        code: NOT_CALLABLE,
+        title: "value is not callable",
+        description: "\
+Only functions, methods and classes can be called.
+
+    var a = 1;
+    a();
+
+Check that the name refers to a function.",
+    },
+    Explanation {
+        code: ARITY_MISMATCH,
+        title: "wrong number of arguments",
+        description: "\
+A function was called with a different number of arguments than it declares
+parameters. A class is called with as many arguments as its `init` takes.
+
+    fun add(a, b) { return a + b; }
+    add(1);
+
+Pass every argument: `add(1, 2);`",
+    },
+    Explanation {
+        code: NOT_AN_INSTANCE,
+        title: "property access on a non-instance",
+        description: "\
+Only instances of classes have properties and fields.
+
+    var a = 1;
+    print a.b;
+    a.b = 2;
+
+Create an instance first: `class A {} var a = A(); a.b = 2;`",
+    },
+    Explanation {
+        code: UNDEFINED_PROPERTY,
+        title: "undefined property",
+        description: "\
+The instance has no field with this name, and neither its class nor any of
+its superclasses has a method with it.
+
+    class A {}
+    print A().b;
+
+Set the field before reading it, or add the method to the class.",
+    },
+    Explanation {
+        code: SUPERCLASS_NOT_A_CLASS,
+        title: "superclass must be a class",
+        description: "\
+The value after `<` in a class declaration has to be a class.
+
+    var Base = 1;
+    class A < Base {}
+
+Inherit from a class declared with `class`.",
+    },
+    Explanation {
+        code: STACK_OVERFLOW,
+        title: "stack overflow",
+        description: "\
+Too many calls were in progress at once, at most 10000 may be. This is almost
+always a recursive function that never reaches the case ending the recursion.
+
+    fun count(n) { return count(n + 1); }
+    count(0);
+
+Make sure every recursive call gets closer to a case that returns without
+calling again, or turn the recursion into a loop.",
+    },
+    Explanation {
+        code: OUTPUT_FAILED,
+        title: "output failed",
+        description: "\
+A `print` could not write its value, usually because whatever was reading the
+output went away, e.g. a pipe into a command that exited early.
+
+    rlox run script.lox | head -n 1
+
+Nothing is wrong with the code itself; make sure the output stays open for as
+long as the script prints to it.",
+    },
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_every_code_is_explained_once() {
+        let codes: HashSet<_> = ALL.iter().collect();
+        assert_eq!(codes.len(), ALL.len(), "a code is declared twice");
+        for &code in ALL {
+            assert!(explain(code).is_some(), "{code} has no explanation");
+        }
+        // and nothing is explained that isn't declared
+        assert_eq!(CATALOG.len(), ALL.len());
+
+        let explanation = explain("E0102".parse().unwrap()).unwrap();
+        assert_eq!(explanation.title, "expected ')'");
+        assert!(explain(Code(9999)).is_none());
+    }
+}
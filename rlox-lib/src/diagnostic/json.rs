@@ -0,0 +1,89 @@
+use std::fmt::Write;
+
+use super::{render::position, Diagnostic, Span};
+
+impl Diagnostic {
+    /// A single line JSON object describing the diagnostic, for tools reading our output.
+    /// `line` and `column` are 1-based and resolved against `source`, `file` names it
+    pub fn to_json(&self, source: &str, file: &str) -> String {
+        let position = position(source, self.span);
+        let mut out = String::from("{");
+        let _ = write!(out, "\"file\":{},", escape(file));
+        let _ = write!(out, "\"line\":{},", position.line);
+        let _ = write!(out, "\"column\":{},", position.col + 1);
+        let _ = write!(out, "\"span\":{},", span(self.span));
+        let _ = write!(out, "\"severity\":\"{}\",", self.severity.name());
+        let _ = write!(out, "\"code\":\"{}\",", self.code);
+        let _ = write!(out, "\"message\":{},", escape(&self.message));
+
+        let labels: Vec<_> = self
+            .labels
+            .iter()
+            .map(|label| {
+                format!(
+                    "{{\"span\":{},\"message\":{}}}",
+                    span(label.span),
+                    escape(&label.message)
+                )
+            })
+            .collect();
+        let _ = write!(out, "\"labels\":[{}],", labels.join(","));
+        let notes: Vec<_> = self.notes.iter().map(|note| escape(note)).collect();
+        let _ = write!(out, "\"notes\":[{}]", notes.join(","));
+        out.push('}');
+        out
+    }
+}
+
+fn span(span: Span) -> String {
+    format!("{{\"start\":{},\"end\":{}}}", span.start, span.end)
+}
+
+/// `text` as a quoted JSON string
+fn escape(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use crate::runner::run;
+
+    #[test]
+    fn test_json_lines() {
+        let source = "{ var a;\n  var a; }\nprint \"tab\there\" +;";
+        let err = run(source, None).unwrap_err();
+        let lines: Vec<_> = err
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.to_json(source, "dir/main.lox"))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"file":"dir/main.lox","line":3,"column":19,"span":{"start":38,"end":39},"severity":"error","code":"E0101","message":"Expect expression.","labels":[],"notes":[]}"#
+            ]
+        );
+
+        let source = "{ var a;\n  var a; }";
+        let err = run(source, None).unwrap_err();
+        assert_eq!(
+            err.diagnostics()[0].to_json(source, "a \"quoted\"\\name"),
+            r#"{"file":"a \"quoted\"\\name","line":2,"column":7,"span":{"start":15,"end":16},"severity":"error","code":"E0202","message":"Already a variable with this name in this scope.","labels":[{"span":{"start":6,"end":7},"message":"previously declared here"}],"notes":[]}"#
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Structured problems reported by every stage of running lox code.
+//!
+//! A [`Diagnostic`] only holds data, how it is shown (a terminal, JSON, an editor)
+//! is left to whoever ends up with it
+
+pub mod codes;
+mod json;
+mod render;
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::tokens::Loc;
+pub use crate::tokens::Span;
+pub use render::Renderer;
+
+/// How serious a [`Diagnostic`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    /// Lowercase name, as used in rendered output
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "Error"),
+            Severity::Warning => write!(f, "Warning"),
+            Severity::Note => write!(f, "Note"),
+        }
+    }
+}
+
+/// Identifies a kind of problem independently of how its message is worded,
+/// shown as `E0001`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Code(pub u16);
+
+impl Display for Code {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "E{:04}", self.0)
+    }
+}
+
+/// Reads codes as shown, `E0102`, the `E` may be left out or lowercase
+impl FromStr for Code {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix(['E', 'e']).unwrap_or(s);
+        if digits.len() != 4 {
+            return Err(format!("'{s}' is not an error code, they look like E0102"));
+        }
+        digits
+            .parse()
+            .map(Code)
+            .map_err(|_| format!("'{s}' is not an error code, they look like E0102"))
+    }
+}
+
+/// A secondary piece of code a [`Diagnostic`] points at, e.g. an earlier declaration
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Code,
+    pub message: String,
+    /// Line the primary span starts on
+    pub line: usize,
+    /// 1-based column the primary span starts at, counted in chars. Rendered
+    /// and JSON output report the same line and column
+    pub col: usize,
+    /// The code the diagnostic is about
+    pub span: Span,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    /// An error of the kind `code`, found at `loc`. See [`codes`] for the possible codes
+    pub(crate) fn error(loc: Loc, code: Code, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            line: loc.line,
+            col: loc.col,
+            span: loc.span,
+            labels: vec![],
+            notes: vec![],
+        }
+    }
+
+    pub(crate) fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub(crate) fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+}
+
+/// The plain one line form, `[line: 1 column: 4] Error: Expect expression.`,
+/// followed by a line per note
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[line: {} column: {}] {}: {}",
+            self.line, self.col, self.severity, self.message
+        )?;
+        for note in &self.notes {
+            write!(f, "\n\tnote: {note}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let loc = Loc {
+            line: 3,
+            col: 7,
+            span: Span::new(20, 24),
+        };
+        let diagnostic =
+            Diagnostic::error(loc, codes::UNDEFINED_VARIABLE, "Undefined variable 'nope'.");
+        assert_eq!(
+            diagnostic.to_string(),
+            "[line: 3 column: 7] Error: Undefined variable 'nope'."
+        );
+        assert_eq!(diagnostic.span, Span::new(20, 24));
+
+        let diagnostic = diagnostic
+            .with_label(Span::new(0, 4), "declared here")
+            .with_note("globals must be defined before use");
+        assert_eq!(diagnostic.labels[0].message, "declared here");
+        assert_eq!(
+            diagnostic.to_string(),
+            "[line: 3 column: 7] Error: Undefined variable 'nope'.\n\tnote: globals must be defined before use"
+        );
+        assert_eq!(diagnostic.code.to_string(), "E0302");
+    }
+
+    #[test]
+    fn test_parse_code() {
+        assert_eq!("E0102".parse(), Ok(Code(102)));
+        assert_eq!("e0001".parse(), Ok(Code(1)));
+        assert_eq!("0301".parse(), Ok(Code(301)));
+        assert!("E102".parse::<Code>().is_err());
+        assert!("Exxxx".parse::<Code>().is_err());
+    }
+}
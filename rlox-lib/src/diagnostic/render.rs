@@ -0,0 +1,318 @@
+use std::{fmt::Write, io::IsTerminal};
+
+use super::{Diagnostic, Severity, Span};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// Draws a [`Diagnostic`] for a terminal, with the source lines it points at
+/// and carets under the offending code
+///
+/// ```text
+/// error[E0106]: Expect ';' after value.
+///  --> main.lox:1:12
+///   |
+/// 1 | print 1 + 2
+///   |            ^
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Renderer {
+    color: bool,
+}
+
+/// A span to underline, along with what to say about it
+struct Annotation<'a> {
+    span: Span,
+    message: Option<&'a str>,
+    primary: bool,
+}
+
+/// Where a span starts, resolved against the source
+pub(super) struct Position<'src> {
+    /// 1-based
+    pub(super) line: usize,
+    /// 0-based, counted in chars
+    pub(super) col: usize,
+    text: &'src str,
+    /// Byte offset of the start of `text`
+    line_start: usize,
+    /// Byte offset the span is drawn from
+    offset: usize,
+}
+
+impl Renderer {
+    pub fn new(color: bool) -> Self {
+        Self { color }
+    }
+
+    /// Colors the output only when stderr is a terminal and `NO_COLOR` isn't set
+    pub fn for_stderr() -> Self {
+        let color = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        Self::new(color)
+    }
+
+    /// Renders `diagnostic` against the `source` it was found in, `path` names that source
+    pub fn render(&self, diagnostic: &Diagnostic, source: &str, path: &str) -> String {
+        let mut annotations = vec![Annotation {
+            span: diagnostic.span,
+            message: None,
+            primary: true,
+        }];
+        annotations.extend(diagnostic.labels.iter().map(|label| Annotation {
+            span: label.span,
+            message: Some(&label.message),
+            primary: false,
+        }));
+
+        let mut positions: Vec<_> = annotations
+            .iter()
+            .map(|annotation| (position(source, annotation.span), annotation))
+            .collect();
+        let primary = &positions[0].0;
+        let (line, col) = (primary.line, primary.col + 1);
+        positions.sort_by_key(|(position, _)| position.line);
+        let gutter = " ".repeat(positions.last().map_or(1, |(pos, _)| digits(pos.line)));
+
+        let mut out = String::new();
+        let color = match diagnostic.severity {
+            Severity::Error => "\x1b[1;31m",
+            Severity::Warning => "\x1b[1;33m",
+            Severity::Note => "\x1b[1;36m",
+        };
+        let title = format!("{}[{}]", diagnostic.severity.name(), diagnostic.code);
+        out.push_str(&self.paint(color, &title));
+        let _ = writeln!(
+            out,
+            "{}",
+            self.paint(BOLD, &format!(": {}", diagnostic.message))
+        );
+        let _ = writeln!(
+            out,
+            "{gutter}{} {path}:{line}:{col}",
+            self.paint(BLUE, "-->")
+        );
+        let _ = writeln!(out, "{gutter} {}", self.paint(BLUE, "|"));
+
+        let mut prev_line = None;
+        for (position, annotation) in &positions {
+            if prev_line != Some(position.line) {
+                if prev_line.is_some_and(|prev| position.line > prev + 1) {
+                    let _ = writeln!(out, "{}", self.paint(BLUE, "..."));
+                }
+                let number = format!("{:>width$} |", position.line, width = gutter.len());
+                let _ = writeln!(out, "{} {}", self.paint(BLUE, &number), position.text);
+                prev_line = Some(position.line);
+            }
+            out.push_str(&self.underline(source, position, annotation, &gutter, color));
+        }
+
+        for note in &diagnostic.notes {
+            let _ = writeln!(out, "{gutter} {} note: {note}", self.paint(BLUE, "="));
+        }
+        out
+    }
+
+    /// The row of `^` or `-` marks under an annotated span, followed by its message
+    fn underline(
+        &self,
+        source: &str,
+        position: &Position,
+        annotation: &Annotation,
+        gutter: &str,
+        color: &str,
+    ) -> String {
+        // copy tabs so the marks line up however wide the terminal draws them
+        let pad: String = position
+            .text
+            .chars()
+            .take(position.col)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let line_end = position.line_start + position.text.len();
+        let start = position.offset;
+        let end = clamp(source, annotation.span.end.min(line_end)).max(start);
+        let width = source[start..end].chars().count().max(1);
+
+        let (mark, color) = if annotation.primary {
+            ('^', color)
+        } else {
+            ('-', BLUE)
+        };
+        let mut marks = mark.to_string().repeat(width);
+        if let Some(message) = annotation.message {
+            marks.push(' ');
+            marks.push_str(message);
+        }
+        format!(
+            "{gutter} {} {pad}{}\n",
+            self.paint(BLUE, "|"),
+            self.paint(color, &marks)
+        )
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{color}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Resolves the start of `span` to a line and column of `source`
+pub(super) fn position(source: &str, span: Span) -> Position<'_> {
+    let mut offset = clamp(source, span.start);
+    // point right after the last token rather than at trailing blank lines
+    if span.start == span.end && offset >= source.trim_end().len() {
+        offset = source.trim_end().len();
+    }
+    let line_start = source[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+    let line_end = source[offset..]
+        .find('\n')
+        .map_or(source.len(), |idx| offset + idx);
+    Position {
+        line: source[..line_start].matches('\n').count() + 1,
+        col: source[line_start..offset].chars().count(),
+        text: source[line_start..line_end].trim_end_matches('\r'),
+        line_start,
+        offset,
+    }
+}
+
+/// Keeps a byte offset inside `source` and on a char boundary
+fn clamp(source: &str, mut offset: usize) -> usize {
+    offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+fn digits(mut num: usize) -> usize {
+    let mut count = 1;
+    while num >= 10 {
+        num /= 10;
+        count += 1;
+    }
+    count
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        diagnostic::{codes, Code},
+        runner::RunError,
+        tokens::Loc,
+    };
+
+    fn diagnostic(span: Span, code: Code, message: &str) -> Diagnostic {
+        let loc = Loc {
+            line: 0,
+            col: 0,
+            span,
+        };
+        Diagnostic::error(loc, code, message)
+    }
+
+    #[test]
+    fn test_snippet_and_caret() {
+        let source = "var a = 1;\nprint a +;\n";
+        let rendered = Renderer::new(false).render(
+            &diagnostic(
+                Span::new(20, 21),
+                codes::EXPECTED_EXPRESSION,
+                "Expect expression.",
+            ),
+            source,
+            "main.lox",
+        );
+        let expected = [
+            "error[E0101]: Expect expression.",
+            " --> main.lox:2:10",
+            "  |",
+            "2 | print a +;",
+            "  |          ^",
+            "",
+        ];
+        assert_eq!(rendered, expected.join("\n"));
+    }
+
+    #[test]
+    fn test_labels_and_notes() {
+        let source = "{\n  var a;\n  var a;\n}";
+        let diagnostic = diagnostic(
+            Span::new(17, 18),
+            codes::DUPLICATE_VARIABLE,
+            "Already a variable with this name.",
+        )
+        .with_label(Span::new(8, 9), "previously declared here")
+        .with_note("shadow it in an inner block instead");
+        let rendered = Renderer::new(false).render(&diagnostic, source, "main.lox");
+        let expected = [
+            "error[E0202]: Already a variable with this name.",
+            " --> main.lox:3:7",
+            "  |",
+            "2 |   var a;",
+            "  |       - previously declared here",
+            "3 |   var a;",
+            "  |       ^",
+            "  = note: shadow it in an inner block instead",
+            "",
+        ];
+        assert_eq!(rendered, expected.join("\n"));
+    }
+
+    #[test]
+    fn test_end_of_input_and_color() {
+        let source = "print 1\n\n";
+        let diagnostic = diagnostic(
+            Span::new(9, 9),
+            codes::EXPECTED_SEMICOLON,
+            "Expect ';' after value.",
+        );
+        let rendered = Renderer::new(false).render(&diagnostic, source, "main.lox");
+        assert!(rendered.contains("1 | print 1\n  |        ^\n"));
+
+        let rendered = Renderer::new(true).render(&diagnostic, source, "main.lox");
+        assert!(rendered.starts_with("\x1b[1;31merror[E0106]\x1b[0m"));
+    }
+
+    #[test]
+    fn test_every_output_agrees_on_the_location() {
+        let sources = [
+            "var a = 1;\n  print a @;",
+            "print \"never\nclosed;",
+            "/* open\n/* nested */",
+            "print 1\n\n",
+            "var 1 = 2;",
+            "fun f() {}\n\treturn f;",
+            "var x = 1;\nprint \"é\" + x;",
+        ];
+        for source in sources {
+            let err = crate::runner::run(source, None).unwrap_err();
+            for diagnostic in err.diagnostics() {
+                let position = position(source, diagnostic.span);
+                let rendered = Renderer::new(false).render(diagnostic, source, "main.lox");
+                let json = diagnostic.to_json(source, "main.lox");
+                let (line, col) = (diagnostic.line, diagnostic.col);
+                assert_eq!((position.line, position.col + 1), (line, col), "{source:?}");
+                assert!(rendered.contains(&format!("main.lox:{line}:{col}\n")));
+                assert!(json.contains(&format!(r#""line":{line},"column":{col},"#)));
+                assert!(diagnostic
+                    .to_string()
+                    .starts_with(&format!("[line: {line} column: {col}]")));
+            }
+        }
+    }
+
+    #[test]
+    fn test_real_errors() {
+        let source = "var x = 1;\nprint x + \"a\";";
+        let err = crate::runner::run(source, None).unwrap_err();
+        assert!(matches!(err, RunError::Runtime(_)));
+        let rendered = Renderer::new(false).render(&err.diagnostics()[0], source, "main.lox");
+        assert!(rendered.contains("2 | print x + \"a\";\n  |         ^\n"));
+    }
+}
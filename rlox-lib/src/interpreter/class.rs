@@ -0,0 +1,108 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use super::{
+    function::LoxFunction,
+    value::{Callable, Value},
+    Interpreter,
+};
+use crate::{
+    diagnostic::{codes, Diagnostic},
+    tokens::Loc,
+};
+
+/// The name of the method run when a class is called
+pub(crate) const INITIALIZER: &str = "init";
+
+#[derive(Debug)]
+pub(crate) struct LoxClass {
+    pub(crate) name: Rc<str>,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<Rc<str>, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub(crate) fn new(
+        name: Rc<str>,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<Rc<str>, Rc<LoxFunction>>,
+    ) -> Self {
+        Self {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    /// Looks `name` up on this class, then on its superclasses
+    pub(crate) fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(name) {
+            Some(method) => Some(method.clone()),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+}
+
+impl Callable for LoxClass {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn arity(&self) -> usize {
+        self.find_method(INITIALIZER).map_or(0, |init| init.arity())
+    }
+
+    fn call(
+        self: Rc<Self>,
+        interpreter: &Interpreter,
+        args: Vec<Value>,
+    ) -> Result<Value, Diagnostic> {
+        let instance = Value::Instance(Rc::new(RefCell::new(Instance::new(self.clone()))));
+        if let Some(init) = self.find_method(INITIALIZER) {
+            Rc::new(init.bind(instance.clone())).call(interpreter, args)?;
+        }
+        Ok(instance)
+    }
+}
+
+/// An object created from a class, holding its own fields
+#[derive(Debug)]
+pub(crate) struct Instance {
+    pub(crate) class: Rc<LoxClass>,
+    fields: HashMap<Rc<str>, Value>,
+}
+
+impl Instance {
+    pub(crate) fn new(class: Rc<LoxClass>) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Fields shadow methods, methods come back bound to `instance`
+    pub(crate) fn get(
+        instance: &Rc<RefCell<Instance>>,
+        name: &str,
+        loc: Loc,
+    ) -> Result<Value, Diagnostic> {
+        if let Some(value) = instance.borrow().fields.get(name) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(name);
+        match method {
+            Some(method) => Ok(Value::Callable(Rc::new(
+                method.bind(Value::Instance(instance.clone())),
+            ))),
+            None => Err(Diagnostic::error(
+                loc,
+                codes::UNDEFINED_PROPERTY,
+                format!("Undefined property '{name}'."),
+            )),
+        }
+    }
+
+    pub(crate) fn set(&mut self, name: Rc<str>, value: Value) {
+        self.fields.insert(name, value);
+    }
+}
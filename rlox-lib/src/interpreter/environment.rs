@@ -0,0 +1,157 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use super::Value;
+use crate::{
+    diagnostic::{codes, Diagnostic},
+    tokens::Loc,
+};
+
+/// One scope worth of variables, chained to the scope it is nested in
+#[derive(Debug, Default)]
+pub(crate) struct Environment {
+    values: HashMap<Rc<str>, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// Binds `name` in this scope, shadowing or overwriting any previous binding
+    pub(crate) fn define(&mut self, name: Rc<str>, value: Value) {
+        self.values.insert(name, value);
+    }
+
+    pub(crate) fn get(&self, name: &str, loc: Loc) -> Result<Value, Diagnostic> {
+        if let Some(value) = self.values.get(name) {
+            return Ok(value.clone());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name, loc),
+            None => Err(Self::undefined(name, loc)),
+        }
+    }
+
+    /// Updates the closest existing binding of `name`, never creates a new one
+    pub(crate) fn assign(&mut self, name: &str, value: Value, loc: Loc) -> Result<(), Diagnostic> {
+        if let Some(slot) = self.values.get_mut(name) {
+            *slot = value;
+            return Ok(());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value, loc),
+            None => Err(Self::undefined(name, loc)),
+        }
+    }
+
+    /// Reads `name` from the scope `depth` levels up, as computed by the resolver
+    pub(crate) fn get_at(
+        env: &Rc<RefCell<Environment>>,
+        depth: usize,
+        name: &str,
+        loc: Loc,
+    ) -> Result<Value, Diagnostic> {
+        let ancestor = Self::ancestor(env, depth);
+        let value = ancestor.borrow().values.get(name).cloned();
+        value.ok_or_else(|| Self::undefined(name, loc))
+    }
+
+    pub(crate) fn assign_at(
+        env: &Rc<RefCell<Environment>>,
+        depth: usize,
+        name: &str,
+        value: Value,
+        loc: Loc,
+    ) -> Result<(), Diagnostic> {
+        let ancestor = Self::ancestor(env, depth);
+        let mut ancestor = ancestor.borrow_mut();
+        match ancestor.values.get_mut(name) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => Err(Self::undefined(name, loc)),
+        }
+    }
+
+    fn ancestor(env: &Rc<RefCell<Environment>>, depth: usize) -> Rc<RefCell<Environment>> {
+        let mut curr = env.clone();
+        for _ in 0..depth {
+            let enclosing = curr.borrow().enclosing.clone();
+            match enclosing {
+                Some(enclosing) => curr = enclosing,
+                // the resolver and the interpreter disagree about scopes, the lookup will fail
+                None => break,
+            }
+        }
+        curr
+    }
+
+    fn undefined(name: &str, loc: Loc) -> Diagnostic {
+        let message = format!("Undefined variable '{name}'.");
+        Diagnostic::error(loc, codes::UNDEFINED_VARIABLE, message)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_shadowing_and_assignment() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define(Rc::from("a"), Value::Num(1.0));
+        globals.borrow_mut().define(Rc::from("b"), Value::Num(2.0));
+
+        let mut inner = Environment::with_enclosing(globals.clone());
+        inner.define(Rc::from("a"), Value::Num(10.0));
+
+        assert_eq!(inner.get("a", Loc::default()), Ok(Value::Num(10.0)));
+        assert_eq!(inner.get("b", Loc::default()), Ok(Value::Num(2.0)));
+
+        inner.assign("b", Value::Num(3.0), Loc::default()).unwrap();
+        assert_eq!(
+            globals.borrow().get("b", Loc::default()),
+            Ok(Value::Num(3.0))
+        );
+        assert_eq!(
+            globals.borrow().get("a", Loc::default()),
+            Ok(Value::Num(1.0))
+        );
+
+        let err = inner.assign("c", Value::Nil, Loc::default()).unwrap_err();
+        assert_eq!(err.message, "Undefined variable 'c'.");
+    }
+
+    #[test]
+    fn test_access_at_depth() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define(Rc::from("a"), Value::Num(1.0));
+        let inner = Rc::new(RefCell::new(Environment::with_enclosing(globals.clone())));
+        inner.borrow_mut().define(Rc::from("a"), Value::Num(2.0));
+
+        assert_eq!(
+            Environment::get_at(&inner, 0, "a", Loc::default()),
+            Ok(Value::Num(2.0))
+        );
+        assert_eq!(
+            Environment::get_at(&inner, 1, "a", Loc::default()),
+            Ok(Value::Num(1.0))
+        );
+
+        Environment::assign_at(&inner, 1, "a", Value::Num(3.0), Loc::default()).unwrap();
+        assert_eq!(
+            globals.borrow().get("a", Loc::default()),
+            Ok(Value::Num(3.0))
+        );
+        assert!(Environment::get_at(&inner, 0, "b", Loc::default()).is_err());
+    }
+}
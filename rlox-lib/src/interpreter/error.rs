@@ -0,0 +1,16 @@
+use super::Value;
+use crate::diagnostic::Diagnostic;
+
+/// Why running statements stopped before reaching the end of them
+#[derive(Debug)]
+pub(crate) enum Unwind {
+    /// A `return` statement handing a value back to the closest call
+    Return(Value),
+    Error(Diagnostic),
+}
+
+impl From<Diagnostic> for Unwind {
+    fn from(value: Diagnostic) -> Self {
+        Unwind::Error(value)
+    }
+}
@@ -0,0 +1,118 @@
+use std::{cell::RefCell, rc::Rc, time::SystemTime};
+
+use super::{
+    environment::Environment,
+    error::Unwind,
+    value::{Callable, Value},
+    Interpreter,
+};
+use crate::{ast::Function, diagnostic::Diagnostic, tokens::Loc};
+
+/// A function declared in lox, together with the scope it was declared in
+#[derive(Debug)]
+pub(crate) struct LoxFunction {
+    declaration: Rc<Function>,
+    closure: Rc<RefCell<Environment>>,
+    /// Initializers always hand back `this`, whatever they return
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub(crate) fn new(
+        declaration: Rc<Function>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        Self {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    /// Creates a copy of this method where `this` refers to `instance`
+    pub(crate) fn bind(&self, instance: Value) -> LoxFunction {
+        let mut env = Environment::with_enclosing(self.closure.clone());
+        env.define(Rc::from("this"), instance);
+        LoxFunction::new(
+            self.declaration.clone(),
+            Rc::new(RefCell::new(env)),
+            self.is_initializer,
+        )
+    }
+
+    fn this(&self) -> Result<Value, Diagnostic> {
+        self.closure.borrow().get("this", Loc::default())
+    }
+}
+
+impl Callable for LoxFunction {
+    fn name(&self) -> &str {
+        &self.declaration.name
+    }
+
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(
+        self: Rc<Self>,
+        interpreter: &Interpreter,
+        args: Vec<Value>,
+    ) -> Result<Value, Diagnostic> {
+        let mut env = Environment::with_enclosing(self.closure.clone());
+        for (param, arg) in self.declaration.params.iter().zip(args) {
+            env.define(param.name.clone(), arg);
+        }
+
+        match interpreter.execute_block(&self.declaration.body, env) {
+            Ok(()) | Err(Unwind::Return(_)) if self.is_initializer => self.this(),
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(err)) => Err(err),
+        }
+    }
+}
+
+/// A function implemented in rust and available to every lox program
+#[derive(Debug)]
+pub(crate) struct NativeFunction {
+    name: &'static str,
+    arity: usize,
+    func: fn(&[Value]) -> Value,
+}
+
+impl NativeFunction {
+    /// All natives, to be defined as globals
+    pub(crate) fn all() -> Vec<NativeFunction> {
+        vec![NativeFunction {
+            name: "clock",
+            arity: 0,
+            func: |_| {
+                let secs = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|since| since.as_secs_f64())
+                    .unwrap_or_default();
+                Value::Num(secs)
+            },
+        }]
+    }
+}
+
+impl Callable for NativeFunction {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        self: Rc<Self>,
+        _interpreter: &Interpreter,
+        args: Vec<Value>,
+    ) -> Result<Value, Diagnostic> {
+        Ok((self.func)(&args))
+    }
+}
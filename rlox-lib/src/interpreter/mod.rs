@@ -0,0 +1,916 @@
+mod class;
+mod environment;
+mod error;
+mod function;
+mod value;
+
+pub(crate) use value::Value;
+
+use class::{Instance, LoxClass, INITIALIZER};
+use environment::Environment;
+use error::Unwind;
+use function::{LoxFunction, NativeFunction};
+use value::Callable;
+
+use std::{
+    cell::{Cell, RefCell},
+    io::Write,
+    rc::Rc,
+};
+
+use crate::{
+    ast::{
+        Assign, Bin, Call, Class, Expr, Function, Get, If, Interpolation, Lit, Logical, LogicalOp,
+        Ops, Print, Return, Set, Stmt, StmtVisitor, Super, Un, Var, VarDecl, Visitor, While,
+    },
+    diagnostic::{codes, Diagnostic},
+    tokens::Loc,
+};
+
+type Result<T> = std::result::Result<T, Diagnostic>;
+
+/// Most calls that may be in progress at once. Every lox call takes around 10KB of rust
+/// stack in debug builds, this stays well within the `runner::STACK_SIZE` lox runs with
+const MAX_CALL_DEPTH: usize = 10_000;
+
+/// Tree-walking evaluator for our [`Expr`] and [`Stmt`] trees
+pub(crate) struct Interpreter {
+    /// Where `print` statements write to
+    out: RefCell<Box<dyn Write>>,
+    /// The outermost scope, where anything the resolver left unresolved lives
+    globals: Rc<RefCell<Environment>>,
+    /// The innermost scope of the code currently running
+    environment: RefCell<Rc<RefCell<Environment>>>,
+    /// How many calls are in progress
+    call_depth: Cell<usize>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub(crate) fn new() -> Self {
+        Self::with_output(Box::new(std::io::stdout()))
+    }
+
+    pub(crate) fn with_output(out: Box<dyn Write>) -> Self {
+        let mut globals = Environment::new();
+        for native in NativeFunction::all() {
+            globals.define(Rc::from(native.name()), Value::Callable(Rc::new(native)));
+        }
+
+        let globals = Rc::new(RefCell::new(globals));
+        Self {
+            out: RefCell::new(out),
+            environment: RefCell::new(globals.clone()),
+            globals,
+            call_depth: Cell::new(0),
+        }
+    }
+
+    pub(crate) fn interpret(&self, expr: &Expr) -> Result<Value> {
+        expr.accept(self)
+    }
+
+    pub(crate) fn execute(&self, stmts: &[Stmt]) -> Result<()> {
+        match self.execute_stmts(stmts) {
+            // a `return` outside of any function just ends the program
+            Ok(()) | Err(Unwind::Return(_)) => Ok(()),
+            Err(Unwind::Error(err)) => Err(err),
+        }
+    }
+
+    /// Writes `value` on a line of its own, blaming `loc` if the output is gone
+    pub(crate) fn print(&self, value: &Value, loc: Loc) -> Result<()> {
+        writeln!(self.out.borrow_mut(), "{value}").map_err(|err| {
+            let message = format!("Could not write output: {err}.");
+            Diagnostic::error(loc, codes::OUTPUT_FAILED, message)
+        })
+    }
+
+    fn execute_stmts(&self, stmts: &[Stmt]) -> std::result::Result<(), Unwind> {
+        for stmt in stmts {
+            stmt.accept(self)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `stmts` inside `env`, restoring the current scope afterwards even on errors
+    fn execute_block(&self, stmts: &[Stmt], env: Environment) -> std::result::Result<(), Unwind> {
+        let previous = self.environment.replace(Rc::new(RefCell::new(env)));
+        let res = self.execute_stmts(stmts);
+        self.environment.replace(previous);
+        res
+    }
+
+    /// Reads a variable from the scope the resolver bound it to, or from the globals
+    fn look_up(&self, name: &str, depth: Option<usize>, loc: Loc) -> Result<Value> {
+        match depth {
+            Some(depth) => Environment::get_at(&self.environment.borrow(), depth, name, loc),
+            None => self.globals.borrow().get(name, loc),
+        }
+    }
+
+    fn numbers(left: &Value, right: &Value, loc: Loc) -> Result<(f64, f64)> {
+        match (left, right) {
+            (Value::Num(l), Value::Num(r)) => Ok((*l, *r)),
+            _ => Err(Diagnostic::error(
+                loc,
+                codes::WRONG_OPERAND_TYPE,
+                "Operands must be numbers.",
+            )),
+        }
+    }
+}
+
+impl Visitor for Interpreter {
+    type Output = Result<Value>;
+
+    fn visit_binary(&self, expr: &Bin) -> Self::Output {
+        let left = expr.left.accept(self)?;
+        let right = expr.right.accept(self)?;
+
+        Ok(match expr.op {
+            Ops::Plus => match (&left, &right) {
+                (Value::Num(l), Value::Num(r)) => Value::Num(l + r),
+                (Value::Str(l), Value::Str(r)) => Value::Str(Rc::from(format!("{l}{r}"))),
+                _ => {
+                    return Err(Diagnostic::error(
+                        expr.loc,
+                        codes::WRONG_OPERAND_TYPE,
+                        "Operands must be two numbers or two strings.",
+                    ))
+                }
+            },
+            Ops::Minus => {
+                let (l, r) = Self::numbers(&left, &right, expr.loc)?;
+                Value::Num(l - r)
+            }
+            Ops::Star => {
+                let (l, r) = Self::numbers(&left, &right, expr.loc)?;
+                Value::Num(l * r)
+            }
+            Ops::Slash => {
+                let (l, r) = Self::numbers(&left, &right, expr.loc)?;
+                Value::Num(l / r)
+            }
+            Ops::Greater => {
+                let (l, r) = Self::numbers(&left, &right, expr.loc)?;
+                Value::Bool(l > r)
+            }
+            Ops::GreaterEqual => {
+                let (l, r) = Self::numbers(&left, &right, expr.loc)?;
+                Value::Bool(l >= r)
+            }
+            Ops::Less => {
+                let (l, r) = Self::numbers(&left, &right, expr.loc)?;
+                Value::Bool(l < r)
+            }
+            Ops::LessEqual => {
+                let (l, r) = Self::numbers(&left, &right, expr.loc)?;
+                Value::Bool(l <= r)
+            }
+            Ops::EqualEqual => Value::Bool(left == right),
+            Ops::BangEqual => Value::Bool(left != right),
+        })
+    }
+
+    fn visit_unary(&self, expr: &Un) -> Self::Output {
+        let right = expr.inner().accept(self)?;
+        match expr {
+            Un::Minus(..) => match right {
+                Value::Num(num) => Ok(Value::Num(-num)),
+                _ => Err(Diagnostic::error(
+                    expr.loc(),
+                    codes::WRONG_OPERAND_TYPE,
+                    "Operand must be a number.",
+                )),
+            },
+            Un::Bang(..) => Ok(Value::Bool(!right.is_truthy())),
+        }
+    }
+
+    fn visit_literal(&self, expr: &Lit) -> Self::Output {
+        Ok(match expr {
+            Lit::True => Value::Bool(true),
+            Lit::False => Value::Bool(false),
+            Lit::Nil => Value::Nil,
+            Lit::Num(num) => num.into(),
+            Lit::Str(s) => s.into(),
+        })
+    }
+
+    fn visit_grouping(&self, expr: &Rc<Expr>) -> Self::Output {
+        expr.accept(self)
+    }
+
+    fn visit_variable(&self, expr: &Var) -> Self::Output {
+        self.look_up(&expr.name, expr.depth.get(), expr.loc)
+    }
+
+    fn visit_assign(&self, expr: &Assign) -> Self::Output {
+        let value = expr.value.accept(self)?;
+        match expr.depth.get() {
+            Some(depth) => Environment::assign_at(
+                &self.environment.borrow(),
+                depth,
+                &expr.name,
+                value.clone(),
+                expr.loc,
+            )?,
+            None => self
+                .globals
+                .borrow_mut()
+                .assign(&expr.name, value.clone(), expr.loc)?,
+        }
+        Ok(value)
+    }
+
+    fn visit_logical(&self, expr: &Logical) -> Self::Output {
+        let left = expr.left.accept(self)?;
+
+        // short circuit, handing back the operand itself rather than a bool
+        let done = match expr.op {
+            LogicalOp::Or => left.is_truthy(),
+            LogicalOp::And => !left.is_truthy(),
+        };
+        if done {
+            return Ok(left);
+        }
+        expr.right.accept(self)
+    }
+
+    fn visit_call(&self, expr: &Call) -> Self::Output {
+        let callee = expr.callee.accept(self)?;
+        let args = expr
+            .args
+            .iter()
+            .map(|arg| arg.accept(self))
+            .collect::<Result<Vec<_>>>()?;
+
+        let callable: Rc<dyn Callable> = match callee {
+            Value::Callable(callable) => callable,
+            Value::Class(class) => class,
+            _ => {
+                return Err(Diagnostic::error(
+                    expr.loc,
+                    codes::NOT_CALLABLE,
+                    "Can only call functions and classes.",
+                ))
+            }
+        };
+        if args.len() != callable.arity() {
+            return Err(Diagnostic::error(
+                expr.loc,
+                codes::ARITY_MISMATCH,
+                format!(
+                    "Expected {} arguments but got {}.",
+                    callable.arity(),
+                    args.len()
+                ),
+            ));
+        }
+        if self.call_depth.get() == MAX_CALL_DEPTH {
+            return Err(Diagnostic::error(
+                expr.loc,
+                codes::STACK_OVERFLOW,
+                "Stack overflow.",
+            ));
+        }
+        self.call_depth.set(self.call_depth.get() + 1);
+        let res = callable.call(self, args);
+        self.call_depth.set(self.call_depth.get() - 1);
+        res
+    }
+
+    fn visit_get(&self, expr: &Get) -> Self::Output {
+        match expr.object.accept(self)? {
+            Value::Instance(instance) => Instance::get(&instance, &expr.name, expr.loc),
+            _ => Err(Diagnostic::error(
+                expr.loc,
+                codes::NOT_AN_INSTANCE,
+                "Only instances have properties.",
+            )),
+        }
+    }
+
+    fn visit_set(&self, expr: &Set) -> Self::Output {
+        let Value::Instance(instance) = expr.object.accept(self)? else {
+            return Err(Diagnostic::error(
+                expr.loc,
+                codes::NOT_AN_INSTANCE,
+                "Only instances have fields.",
+            ));
+        };
+        let value = expr.value.accept(self)?;
+        instance.borrow_mut().set(expr.name.clone(), value.clone());
+        Ok(value)
+    }
+
+    fn visit_this(&self, expr: &Var) -> Self::Output {
+        self.visit_variable(expr)
+    }
+
+    fn visit_super(&self, expr: &Super) -> Self::Output {
+        let depth = expr.depth.get();
+        let Value::Class(superclass) = self.look_up("super", depth, expr.loc)? else {
+            return Err(Diagnostic::error(
+                expr.loc,
+                codes::SUPERCLASS_NOT_A_CLASS,
+                "Superclass must be a class.",
+            ));
+        };
+        // `this` is always bound in the scope just inside the one holding `super`
+        let this = self.look_up("this", depth.map(|depth| depth - 1), expr.loc)?;
+
+        match superclass.find_method(&expr.method) {
+            Some(method) => Ok(Value::Callable(Rc::new(method.bind(this)))),
+            None => Err(Diagnostic::error(
+                expr.loc,
+                codes::UNDEFINED_PROPERTY,
+                format!("Undefined property '{}'.", expr.method),
+            )),
+        }
+    }
+
+    fn visit_interpolation(&self, expr: &Interpolation) -> Self::Output {
+        let mut res = String::new();
+        for part in &expr.parts {
+            // stringified the same way `print` does it
+            res.push_str(&part.accept(self)?.to_string());
+        }
+        Ok(Value::Str(Rc::from(res)))
+    }
+}
+
+impl StmtVisitor for Interpreter {
+    type Output = std::result::Result<(), Unwind>;
+
+    fn visit_expression_stmt(&self, expr: &Expr) -> Self::Output {
+        expr.accept(self)?;
+        Ok(())
+    }
+
+    fn visit_print_stmt(&self, stmt: &Print) -> Self::Output {
+        let value = stmt.expr.accept(self)?;
+        self.print(&value, stmt.loc)?;
+        Ok(())
+    }
+
+    fn visit_var_stmt(&self, stmt: &VarDecl) -> Self::Output {
+        let value = match &stmt.initializer {
+            Some(initializer) => initializer.accept(self)?,
+            None => Value::Nil,
+        };
+        self.environment
+            .borrow()
+            .borrow_mut()
+            .define(stmt.name.clone(), value);
+        Ok(())
+    }
+
+    fn visit_block_stmt(&self, stmts: &[Stmt]) -> Self::Output {
+        let env = Environment::with_enclosing(self.environment.borrow().clone());
+        self.execute_block(stmts, env)
+    }
+
+    fn visit_if_stmt(&self, stmt: &If) -> Self::Output {
+        if stmt.condition.accept(self)?.is_truthy() {
+            stmt.then_branch.accept(self)
+        } else if let Some(else_branch) = &stmt.else_branch {
+            else_branch.accept(self)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_while_stmt(&self, stmt: &While) -> Self::Output {
+        while stmt.condition.accept(self)?.is_truthy() {
+            stmt.body.accept(self)?;
+        }
+        Ok(())
+    }
+
+    fn visit_function_stmt(&self, stmt: &Rc<Function>) -> Self::Output {
+        let closure = self.environment.borrow().clone();
+        let function = LoxFunction::new(stmt.clone(), closure, false);
+        self.environment
+            .borrow()
+            .borrow_mut()
+            .define(stmt.name.clone(), Value::Callable(Rc::new(function)));
+        Ok(())
+    }
+
+    fn visit_return_stmt(&self, stmt: &Return) -> Self::Output {
+        let value = match &stmt.value {
+            Some(value) => value.accept(self)?,
+            None => Value::Nil,
+        };
+        Err(Unwind::Return(value))
+    }
+
+    fn visit_class_stmt(&self, stmt: &Class) -> Self::Output {
+        let superclass = match &stmt.superclass {
+            Some(var) => match self.visit_variable(var)? {
+                Value::Class(class) => Some(class),
+                _ => {
+                    let message = "Superclass must be a class.";
+                    let err = Diagnostic::error(var.loc, codes::SUPERCLASS_NOT_A_CLASS, message);
+                    return Err(err.into());
+                }
+            },
+            None => None,
+        };
+
+        // methods of a subclass close over a scope holding `super`
+        let mut closure = self.environment.borrow().clone();
+        if let Some(superclass) = &superclass {
+            let mut env = Environment::with_enclosing(closure);
+            env.define(Rc::from("super"), Value::Class(superclass.clone()));
+            closure = Rc::new(RefCell::new(env));
+        }
+
+        let methods = stmt
+            .methods
+            .iter()
+            .map(|method| {
+                let function = LoxFunction::new(
+                    method.clone(),
+                    closure.clone(),
+                    &*method.name == INITIALIZER,
+                );
+                (method.name.clone(), Rc::new(function))
+            })
+            .collect();
+
+        let class = LoxClass::new(stmt.name.clone(), superclass, methods);
+        self.environment
+            .borrow()
+            .borrow_mut()
+            .define(stmt.name.clone(), Value::Class(Rc::new(class)));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parser::Parser, resolver::Resolver, runner::with_big_stack, scanner::Scanner};
+
+    fn eval(code: &str) -> Result<Value> {
+        let tokens = Scanner::new(code).run();
+        let expr = Parser::new(tokens).parse().expect("should parse");
+        Resolver::new().resolve_expr(&expr).expect("should resolve");
+        Interpreter::new().interpret(&expr)
+    }
+
+    /// Collects everything the interpreter prints
+    #[derive(Clone, Default)]
+    struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run(code: &str) -> Result<String> {
+        let tokens = Scanner::new(code).run();
+        let stmts = Parser::new(tokens)
+            .parse_program()
+            .into_result()
+            .expect("should parse");
+        Resolver::new().resolve(&stmts).expect("should resolve");
+        let buf = SharedBuf::default();
+        Interpreter::with_output(Box::new(buf.clone())).execute(&stmts)?;
+        let out = buf.0.borrow().clone();
+        Ok(String::from_utf8(out).expect("output should be utf8"))
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(eval("1 + 2 * 3").unwrap(), Value::Num(7.0));
+        assert_eq!(eval("(1 + 2) * 3").unwrap(), Value::Num(9.0));
+        assert_eq!(eval("10 / 4 - 1").unwrap(), Value::Num(1.5));
+        assert_eq!(eval("-(3)").unwrap(), Value::Num(-3.0));
+    }
+
+    #[test]
+    fn test_string_concat() {
+        assert_eq!(
+            eval(r#""foo" + "bar""#).unwrap(),
+            Value::Str(Rc::from("foobar"))
+        );
+        assert!(eval(r#""foo" + 1"#).is_err());
+    }
+
+    #[test]
+    fn test_interpolation() {
+        assert_eq!(
+            run(r#"var name = "you"; print "Hi ${name}, ${1 + 2} ${nil} ${"${true}"}";"#).unwrap(),
+            "Hi you, 3 nil true\n"
+        );
+    }
+
+    #[test]
+    fn test_truthiness_and_equality() {
+        assert_eq!(eval("!nil").unwrap(), Value::Bool(true));
+        assert_eq!(eval("!0").unwrap(), Value::Bool(false));
+        assert_eq!(eval(r#"!"""#).unwrap(), Value::Bool(false));
+        assert_eq!(eval("nil == nil").unwrap(), Value::Bool(true));
+        assert_eq!(eval(r#"1 == "one""#).unwrap(), Value::Bool(false));
+        assert_eq!(eval("true != false").unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_comparison_only_on_numbers() {
+        assert_eq!(eval("1 < 2").unwrap(), Value::Bool(true));
+        assert_eq!(eval("2 <= 1").unwrap(), Value::Bool(false));
+        assert_eq!(eval("2 >= 2").unwrap(), Value::Bool(true));
+        assert_eq!(eval("1 > 2").unwrap(), Value::Bool(false));
+        assert!(eval(r#"1 < "x""#).is_err());
+        assert!(eval(r#"-"abc""#).is_err());
+    }
+
+    #[test]
+    fn test_runtime_error_location() {
+        let err = eval(r#"1 + 2 < "x""#).unwrap_err();
+        assert_eq!(err.message, "Operands must be numbers.");
+        assert_eq!((err.line, err.col), (1, 7));
+        assert_eq!(
+            err.to_string(),
+            "[line: 1 column: 7] Error: Operands must be numbers."
+        );
+
+        let err = eval(r#"-"abc""#).unwrap_err();
+        assert_eq!(err.message, "Operand must be a number.");
+        assert_eq!((err.line, err.col), (1, 1));
+    }
+
+    #[test]
+    fn test_print_statements() {
+        assert_eq!(run("print 1 + 2;").unwrap(), "3\n");
+        assert_eq!(
+            run("print \"a\" + \"b\";\n1;\nprint nil;\nprint 2 <= 1;").unwrap(),
+            "ab\nnil\nfalse\n"
+        );
+    }
+
+    #[test]
+    fn test_variables_and_scopes() {
+        let code = r#"
+var a = "global a";
+var b = "global b";
+var c = "global c";
+{
+  var a = "outer a";
+  var b = "outer b";
+  {
+    var a = "inner a";
+    print a;
+    print b;
+    print c;
+  }
+  print a;
+  print b;
+  print c;
+}
+print a;
+print b;
+print c;
+"#;
+        assert_eq!(
+            run(code).unwrap(),
+            "inner a\nouter b\nglobal c\nouter a\nouter b\nglobal c\nglobal a\nglobal b\nglobal c\n"
+        );
+    }
+
+    #[test]
+    fn test_assignment() {
+        assert_eq!(
+            run("var a; print a; a = 1; { a = a + 1; var a = 5; } print a;").unwrap(),
+            "nil\n2\n"
+        );
+        assert_eq!(
+            run("var a = 1; var b = a = 3; print a + b;").unwrap(),
+            "6\n"
+        );
+    }
+
+    #[test]
+    fn test_if_else() {
+        assert_eq!(
+            run("if (1 < 2) print \"yes\"; else print \"no\";").unwrap(),
+            "yes\n"
+        );
+        assert_eq!(run("if (nil) print 1; else print 2;").unwrap(), "2\n");
+        assert_eq!(run("if (false) print 1; print 3;").unwrap(), "3\n");
+    }
+
+    #[test]
+    fn test_logical_short_circuit() {
+        assert_eq!(run("print \"hi\" or 2;").unwrap(), "hi\n");
+        assert_eq!(run("print nil or \"yes\";").unwrap(), "yes\n");
+        assert_eq!(run("print nil and nope;").unwrap(), "nil\n");
+        assert_eq!(
+            run("var a = 0; true or (a = 1); false and (a = 2); print a;").unwrap(),
+            "0\n"
+        );
+    }
+
+    #[test]
+    fn test_loops() {
+        assert_eq!(
+            run("var i = 0; while (i < 3) { print i; i = i + 1; }").unwrap(),
+            "0\n1\n2\n"
+        );
+
+        let fib = r#"
+var a = 0;
+var temp;
+for (var b = 1; a < 50; b = temp + b) {
+  print a;
+  temp = a;
+  a = b;
+}
+"#;
+        assert_eq!(run(fib).unwrap(), "0\n1\n1\n2\n3\n5\n8\n13\n21\n34\n");
+
+        // the loop variable is scoped to the loop
+        assert!(run("for (var i = 0; i < 1; i = i + 1) {} print i;").is_err());
+    }
+
+    #[test]
+    fn test_functions_and_return() {
+        let code = r#"
+fun sayHi(first, last) {
+  print "Hi, " + first + " " + last + "!";
+}
+sayHi("Dear", "Reader");
+
+fun fib(n) {
+  if (n <= 1) return n;
+  return fib(n - 2) + fib(n - 1);
+}
+for (var i = 0; i < 8; i = i + 1) {
+  print fib(i);
+}
+
+fun early() {
+  while (true) {
+    return "done";
+  }
+  print "unreachable";
+}
+print early();
+
+fun nothing() {}
+print nothing();
+print sayHi;
+"#;
+        assert_eq!(
+            run(code).unwrap(),
+            "Hi, Dear Reader!\n0\n1\n1\n2\n3\n5\n8\n13\ndone\nnil\n<fn sayHi>\n"
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        let code = r#"
+fun makeCounter() {
+  var i = 0;
+  fun count() {
+    i = i + 1;
+    print i;
+  }
+  return count;
+}
+
+var counter = makeCounter();
+counter();
+counter();
+var other = makeCounter();
+other();
+counter();
+"#;
+        assert_eq!(run(code).unwrap(), "1\n2\n1\n3\n");
+    }
+
+    #[test]
+    fn test_call_errors() {
+        let err = run("fun f(a, b) {}\nf(1);").unwrap_err();
+        assert_eq!(err.message, "Expected 2 arguments but got 1.");
+        assert_eq!((err.line, err.col), (2, 4));
+
+        let err = run(r#""not a function"();"#).unwrap_err();
+        assert_eq!(err.message, "Can only call functions and classes.");
+    }
+
+    #[test]
+    fn test_classes_and_instances() {
+        let code = r#"
+class Bagel {
+  eat() {
+    print "Crunch crunch crunch!";
+  }
+}
+var bagel = Bagel();
+print Bagel;
+print bagel;
+bagel.eat();
+bagel.topping = "cream cheese";
+print bagel.topping;
+"#;
+        assert_eq!(
+            run(code).unwrap(),
+            "Bagel\nBagel instance\nCrunch crunch crunch!\ncream cheese\n"
+        );
+    }
+
+    #[test]
+    fn test_this_and_bound_methods() {
+        let code = r#"
+class Cake {
+  taste() {
+    var adjective = "delicious";
+    print "The " + this.flavor + " cake is " + adjective + "!";
+  }
+}
+var cake = Cake();
+cake.flavor = "German chocolate";
+var taste = cake.taste;
+cake.flavor = "lemon";
+taste();
+
+class Thing {
+  getCallback() {
+    fun localFunction() {
+      print this.name;
+    }
+    return localFunction;
+  }
+}
+var thing = Thing();
+thing.name = "thing";
+thing.getCallback()();
+"#;
+        assert_eq!(run(code).unwrap(), "The lemon cake is delicious!\nthing\n");
+    }
+
+    #[test]
+    fn test_initializers() {
+        let code = r#"
+class Point {
+  init(x, y) {
+    this.x = x;
+    this.y = y;
+    return;
+  }
+  sum() { return this.x + this.y; }
+}
+var p = Point(1, 2);
+print p.sum();
+print p.init(3, 4) == p;
+print p.sum();
+"#;
+        assert_eq!(run(code).unwrap(), "3\ntrue\n7\n");
+
+        let err = run("class P { init(a) {} } P();").unwrap_err();
+        assert_eq!(err.message, "Expected 1 arguments but got 0.");
+    }
+
+    #[test]
+    fn test_inheritance_and_super() {
+        let code = r#"
+class Doughnut {
+  cook() {
+    print "Fry until golden brown.";
+  }
+  name() { return "doughnut"; }
+}
+
+class BostonCream < Doughnut {
+  cook() {
+    super.cook();
+    print "Pipe full of custard and coat with chocolate.";
+  }
+}
+
+class Filled < BostonCream {
+  cook() {
+    super.cook();
+    print "Done with the " + this.name() + ".";
+  }
+}
+
+Filled().cook();
+"#;
+        assert_eq!(
+            run(code).unwrap(),
+            "Fry until golden brown.\nPipe full of custard and coat with chocolate.\nDone with the doughnut.\n"
+        );
+    }
+
+    #[test]
+    fn test_class_runtime_errors() {
+        let err = run("var NotAClass = 1; class A < NotAClass {}").unwrap_err();
+        assert_eq!(err.message, "Superclass must be a class.");
+
+        let err = run("class A {} A().nope;").unwrap_err();
+        assert_eq!(err.message, "Undefined property 'nope'.");
+
+        let err = run("var a = 1; a.b = 2;").unwrap_err();
+        assert_eq!(err.message, "Only instances have fields.");
+
+        let err = run(r#""str".len;"#).unwrap_err();
+        assert_eq!(err.message, "Only instances have properties.");
+    }
+
+    #[test]
+    fn test_native_clock() {
+        assert_eq!(run("print clock() > 0;").unwrap(), "true\n");
+    }
+
+    #[test]
+    fn test_closures_capture_at_declaration() {
+        let code = r#"
+var a = "global";
+{
+  fun showA() {
+    print a;
+  }
+
+  showA();
+  var a = "block";
+  showA();
+}
+"#;
+        assert_eq!(run(code).unwrap(), "global\nglobal\n");
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        let err = run("print nope;").unwrap_err();
+        assert_eq!(err.message, "Undefined variable 'nope'.");
+        assert_eq!((err.line, err.col), (1, 7));
+
+        let err = run("{ var a = 1; } a = 2;").unwrap_err();
+        assert_eq!(err.message, "Undefined variable 'a'.");
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let code = "fun f(n) { if (n == 0) return 0; return f(n - 1) + 1; }";
+        with_big_stack(|| {
+            // ordinary recursion goes thousands of calls deep
+            assert_eq!(run(&format!("{code} print f(1000);")).unwrap(), "1000\n");
+            assert_eq!(run(&format!("{code} print f(9000);")).unwrap(), "9000\n");
+
+            let err = run(&format!("{code} print f(20000);")).unwrap_err();
+            assert_eq!(err.message, "Stack overflow.");
+            assert_eq!(err.code, codes::STACK_OVERFLOW);
+
+            // the depth unwinds with the error, so the same interpreter can call again
+            let parse = |code: &str| {
+                let stmts = Parser::new(Scanner::new(code).run()).parse_program();
+                let stmts = stmts.into_result().expect("should parse");
+                Resolver::new().resolve(&stmts).expect("should resolve");
+                stmts
+            };
+            let interpreter = Interpreter::with_output(Box::new(SharedBuf::default()));
+            let overflow = parse(&format!("{code} f(20000);"));
+            assert!(interpreter.execute(&overflow).is_err());
+            assert!(interpreter.execute(&parse("f(1000);")).is_ok());
+        });
+    }
+
+    #[test]
+    fn test_print_to_closed_output() {
+        struct ClosedPipe;
+
+        impl Write for ClosedPipe {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let stmts = Parser::new(Scanner::new("var a = 1;\n  print a;").run()).parse_program();
+        let stmts = stmts.into_result().expect("should parse");
+        let err = Interpreter::with_output(Box::new(ClosedPipe))
+            .execute(&stmts)
+            .unwrap_err();
+        assert_eq!(err.code, codes::OUTPUT_FAILED);
+        assert_eq!((err.line, err.col), (2, 3));
+    }
+}
@@ -0,0 +1,102 @@
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+use super::{
+    class::{Instance, LoxClass},
+    Interpreter,
+};
+use crate::{diagnostic::Diagnostic, tokens::Literal};
+
+/// Anything that can be invoked with `(...)` at runtime, i.e. functions and classes
+pub(crate) trait Callable: std::fmt::Debug {
+    fn name(&self) -> &str;
+    fn arity(&self) -> usize;
+    fn call(
+        self: Rc<Self>,
+        interpreter: &Interpreter,
+        args: Vec<Value>,
+    ) -> Result<Value, Diagnostic>;
+}
+
+/// A value produced while running a lox program
+#[derive(Debug, Clone)]
+pub(crate) enum Value {
+    Bool(bool),
+    Nil,
+    Num(f64),
+    Str(Rc<str>),
+    Callable(Rc<dyn Callable>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<Instance>>),
+}
+
+impl Value {
+    /// `nil` and `false` are falsey, everything else is truthy
+    pub(crate) fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(value: &Literal) -> Self {
+        match value {
+            Literal::String(s) => Value::Str(s.clone()),
+            Literal::Num(num) => Value::Num(*num),
+            Literal::None => Value::Nil,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Bool(l), Value::Bool(r)) => l == r,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Num(l), Value::Num(r)) => l == r,
+            (Value::Str(l), Value::Str(r)) => l == r,
+            // functions, classes and instances compare by identity
+            (Value::Callable(l), Value::Callable(r)) => Rc::ptr_eq(l, r),
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Nil => write!(f, "nil"),
+            // rust already prints `3.0_f64` as `3`
+            Value::Num(num) => write!(f, "{num}"),
+            Value::Str(s) => write!(f, "{s}"),
+            Value::Callable(callable) => write!(f, "<fn {}>", callable.name()),
+            Value::Class(class) => write!(f, "{}", class.name),
+            Value::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display_follows_lox() {
+        assert_eq!(Value::Num(3.0).to_string(), "3");
+        assert_eq!(Value::Num(-0.5).to_string(), "-0.5");
+        assert_eq!(Value::Str(Rc::from("hi")).to_string(), "hi");
+        assert_eq!(Value::Bool(true).to_string(), "true");
+        assert_eq!(Value::Nil.to_string(), "nil");
+    }
+
+    #[test]
+    fn test_from_literal() {
+        assert_eq!(Value::from(&Literal::Num(1.5)), Value::Num(1.5));
+        assert_eq!(
+            Value::from(&Literal::String(Rc::from("a"))),
+            Value::Str(Rc::from("a"))
+        );
+        assert_eq!(Value::from(&Literal::None), Value::Nil);
+    }
+}
@@ -1,7 +1,29 @@
-mod ast;
-mod parser;
-mod scanner;
-mod tokens;
+//! A tree-walking interpreter for [lox](https://craftinginterpreters.com/).
+//!
+//! [`Lox`] runs code end to end. The stages it is built from are public as well: the
+//! [`scanner`] turns source code into [`tokens`], the [`parser`] builds an [`ast`] out of
+//! them, and every problem found along the way is a [`diagnostic::Diagnostic`]
+//!
+//! ```
+//! use rlox_lib::{Lox, RunError};
+//!
+//! let mut lox = Lox::new();
+//! lox.run_source("fun add(a, b) { return a + b; }").unwrap();
+//! let err = lox.run_source("print add(1);").unwrap_err();
+//! assert!(matches!(err, RunError::Runtime(_)));
+//! assert_eq!(err.diagnostics()[0].code.to_string(), "E0304");
+//! ```
 
-pub mod macros;
+mod interpreter;
+mod lox;
+mod resolver;
+
+pub mod ast;
+pub mod diagnostic;
+pub mod parser;
 pub mod runner;
+pub mod scanner;
+pub mod tokens;
+
+pub use lox::Lox;
+pub use runner::RunError;
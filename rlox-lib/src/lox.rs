@@ -0,0 +1,186 @@
+use std::{
+    fs,
+    io::Write,
+    panic,
+    path::Path,
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    interpreter::Interpreter,
+    runner::{self, RunError},
+};
+
+/// Runs lox code from rust. Whatever one call defines stays around for the next
+///
+/// The code runs on a thread of its own with a big stack, so deeply nested code or deep
+/// recursion can't overflow the stack of the thread calling in
+///
+/// ```
+/// let mut lox = rlox_lib::Lox::new();
+/// lox.run_source("var greeting = \"hello\";").unwrap();
+/// lox.run_source("print greeting;").unwrap();
+/// ```
+pub struct Lox {
+    sources: Sender<String>,
+    results: Receiver<Result<(), RunError>>,
+    /// Owns the interpreter, `None` once its panic was handed on
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lox {
+    /// A `Lox` printing to stdout
+    pub fn new() -> Self {
+        Self::with_output(std::io::stdout())
+    }
+
+    /// A `Lox` whose `print` statements write to `out` instead of stdout
+    ///
+    /// ```
+    /// let mut lox = rlox_lib::Lox::with_output(std::io::sink());
+    /// lox.run_source("print \"nobody sees this\";").unwrap();
+    /// ```
+    pub fn with_output(out: impl Write + Send + 'static) -> Self {
+        let (sources, worker_sources) = mpsc::channel::<String>();
+        let (worker_results, results) = mpsc::channel();
+        let worker = thread::Builder::new()
+            .name("lox".into())
+            .stack_size(runner::STACK_SIZE)
+            .spawn(move || {
+                let interpreter = Interpreter::with_output(Box::new(out));
+                for source in worker_sources {
+                    let res = runner::check(&source)
+                        .and_then(|stmts| interpreter.execute(&stmts).map_err(RunError::Runtime));
+                    if worker_results.send(res).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to spawn a thread to run lox on");
+
+        Self {
+            sources,
+            results,
+            worker: Some(worker),
+        }
+    }
+
+    /// Scans, parses, resolves and runs `source`, stopping at the first stage that reports errors
+    pub fn run_source(&mut self, source: &str) -> Result<(), RunError> {
+        let res = self.sources.send(source.to_owned()).ok();
+        match res.and_then(|()| self.results.recv().ok()) {
+            Some(res) => res,
+            None => self.worker_panicked(),
+        }
+    }
+
+    /// Reads the file at `path` and runs it like [`Lox::run_source`]
+    pub fn run_file(&mut self, path: impl AsRef<Path>) -> Result<(), RunError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|err| RunError::Io {
+            path: path.to_path_buf(),
+            err,
+        })?;
+        self.run_source(&source)
+    }
+
+    /// The worker only stops early by panicking, e.g. in the output it was given.
+    /// Hands that panic on to the caller
+    fn worker_panicked(&mut self) -> ! {
+        let worker = self.worker.take().expect("the lox thread panicked earlier");
+        match worker.join() {
+            Err(panic) => panic::resume_unwind(panic),
+            Ok(()) => unreachable!("the lox thread stopped without panicking"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_state_is_kept_between_runs() {
+        let mut lox = Lox::new();
+        lox.run_source("var a = 1; fun inc() { a = a + 1; return a; }")
+            .unwrap();
+        lox.run_source("if (inc() != 2) nope();").unwrap();
+
+        let err = lox.run_source("print a +;").unwrap_err();
+        assert!(matches!(err, RunError::Parse(_)));
+        // a failed run leaves what was defined before it alone
+        lox.run_source("if (inc() != 3) nope();").unwrap();
+    }
+
+    #[test]
+    fn test_output_is_captured() {
+        let buf = SharedBuf::default();
+        let mut lox = Lox::with_output(buf.clone());
+        lox.run_source("print 1 + 2; print \"done\";").unwrap();
+        assert_eq!(&*buf.0.lock().unwrap(), b"3\ndone\n");
+    }
+
+    #[test]
+    fn test_deep_code_runs_on_a_big_stack() {
+        let buf = SharedBuf::default();
+        let mut lox = Lox::with_output(buf.clone());
+        lox.run_source(&format!("print 0{};", " + 1".repeat(10_000)))
+            .unwrap();
+        let ladder: String = (0..1000)
+            .map(|i| format!("if (x == {i}) print {i}; else "))
+            .collect();
+        lox.run_source(&format!("var x = 999; {ladder} print nil;"))
+            .unwrap();
+        assert_eq!(&*buf.0.lock().unwrap(), b"10000\n999\n");
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        let buf = SharedBuf::default();
+        let mut lox = Lox::with_output(buf.clone());
+        lox.run_source("fun count(n) { if (n > 0) return count(n - 1) + 1; return 0; }")
+            .unwrap();
+        lox.run_source("print count(1000);").unwrap();
+        assert_eq!(&*buf.0.lock().unwrap(), b"1000\n");
+
+        let err = lox.run_source("print count(100000);").unwrap_err();
+        assert_eq!(err.diagnostics()[0].code.to_string(), "E0308");
+    }
+
+    #[test]
+    fn test_run_file() {
+        let mut lox = Lox::new();
+        let err = lox.run_file("does/not/exist.lox").unwrap_err();
+        assert!(matches!(err, RunError::Io { .. }));
+        assert_eq!(err.exit_code(), 66);
+        assert!(err.diagnostics().is_empty());
+        assert_eq!(err.to_string(), "could not read does/not/exist.lox");
+
+        let path = std::env::temp_dir().join(format!("rlox-test-{}.lox", std::process::id()));
+        fs::write(&path, "print undefined;").unwrap();
+        let err = lox.run_file(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(err, RunError::Runtime(_)));
+    }
+}
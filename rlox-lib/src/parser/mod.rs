@@ -1,31 +1,461 @@
-use anyhow::{bail, Result};
+//! Builds the [syntax tree](crate::ast) out of the [scanner's](crate::scanner) tokens.
+//!
+//! The parser recovers at statement boundaries, so a single run reports every syntax error
+
+use std::{cell::Cell, rc::Rc};
 
 use crate::{
-    ast::{Bin, Expr, Lit, Un},
-    err_msg,
+    ast::{
+        Assign, Bin, Call, Class, Expr, Function, Get, If, Interpolation, Lit, Logical, LogicalOp,
+        Ops, Param, Print, Return, Set, Stmt, Super, Un, Var, VarDecl, While,
+    },
+    diagnostic::{codes, Code, Diagnostic},
     scanner::TokenInfo,
-    tokens::{Token, TokenType},
+    tokens::{Literal, Loc, Span, Token, TokenType},
 };
 
-struct Parser {
+type Result<T> = std::result::Result<T, Diagnostic>;
+
+/// Most arguments a call, or parameters a function, may have
+const MAX_ARGS: usize = 255;
+
+/// How deep expressions and statements may nest, counting the links of operator and call
+/// chains too. Each level takes a dozen recursive calls, so code this deep needs far more
+/// stack than threads get by default. Lox code runs on threads with `runner::STACK_SIZE`
+const MAX_DEPTH: usize = 20_000;
+
+/// A recursive descent parser over the tokens of one piece of code
+pub struct Parser {
     cursor: usize,
     tokens: TokenInfo,
+    /// For each class we are inside of, whether it has a superclass
+    classes: Vec<bool>,
+    errors: Vec<Diagnostic>,
+    /// How many expressions and statements we are nested inside of, plus the links of
+    /// the chains among them
+    depth: usize,
+}
+
+/// What the parser made of a program
+#[derive(Debug)]
+pub struct ParseInfo {
+    /// Every statement that parsed, even when there were errors elsewhere
+    pub stmts: Vec<Stmt>,
+    pub errors: Vec<Diagnostic>,
+}
+
+impl ParseInfo {
+    /// The statements, but only if the whole program parsed
+    pub fn into_result(self) -> std::result::Result<Vec<Stmt>, Vec<Diagnostic>> {
+        if self.errors.is_empty() {
+            Ok(self.stmts)
+        } else {
+            Err(self.errors)
+        }
+    }
 }
 
 impl Parser {
-    pub fn new(tokens: TokenInfo) -> Self {
-        Self { cursor: 0, tokens }
+    pub fn new(mut tokens: TokenInfo) -> Self {
+        // the grammar relies on a closing Eof, so the cursor never runs off the end
+        if tokens.tags.last() != Some(&TokenType::Eof) {
+            tokens.tags.push(TokenType::Eof);
+            tokens.tokens.push(Token::new("", false));
+            tokens
+                .line_nrs
+                .push(tokens.line_nrs.last().copied().unwrap_or(1));
+            tokens.cols.push(tokens.cols.last().copied().unwrap_or(1));
+            tokens
+                .end_cols
+                .push(tokens.end_cols.last().copied().unwrap_or_default());
+            let end = tokens.spans.last().map_or(0, |span| span.end);
+            tokens.spans.push(Span::new(end, end));
+        }
+        Self {
+            cursor: 0,
+            tokens,
+            classes: vec![],
+            errors: vec![],
+            depth: 0,
+        }
+    }
+
+    /// Parses a single expression spanning all of the input, as typed into the repl
+    pub fn parse(&mut self) -> std::result::Result<Expr, Vec<Diagnostic>> {
+        match self.expression() {
+            Ok(expr) if self.errors.is_empty() && self.is_at_end() => return Ok(expr),
+            Ok(_) if self.errors.is_empty() => {
+                let err = "Expect end of expression.";
+                self.report(self.cursor, codes::EXPECTED_END, err);
+            }
+            Ok(_) => {}
+            Err(err) => self.errors.push(err),
+        }
+        Err(std::mem::take(&mut self.errors))
+    }
+
+    /// Parses declarations until the end of input, skipping to the next statement after an error
+    pub fn parse_program(&mut self) -> ParseInfo {
+        let mut stmts = vec![];
+        while !self.is_at_end() {
+            if let Some(stmt) = self.declaration() {
+                stmts.push(stmt);
+            }
+        }
+        ParseInfo {
+            stmts,
+            errors: std::mem::take(&mut self.errors),
+        }
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.check(TokenType::Eof)
+    }
+
+    fn check(&self, tok_type: TokenType) -> bool {
+        self.tokens.tags.get(self.cursor) == Some(&tok_type)
+    }
+
+    /// Consumes the current token only if it is of `tok_type`
+    fn match_next(&mut self, tok_type: TokenType) -> bool {
+        if self.check(tok_type) {
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    /// Records the error and resynchronizes when the declaration is malformed
+    fn declaration(&mut self) -> Option<Stmt> {
+        match self.declaration_inner() {
+            Ok(stmt) => Some(stmt),
+            Err(err) => {
+                self.errors.push(err);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn declaration_inner(&mut self) -> Result<Stmt> {
+        let start = self.cursor;
+        if self.match_next(TokenType::Class) {
+            return self.class_declaration();
+        }
+        if self.match_next(TokenType::Fun) {
+            let mut function = self.function("function")?;
+            // methods start at their name, declarations at `fun`
+            function.span = self.span_from(start);
+            return Ok(Stmt::Function(function.into()));
+        }
+        if self.match_next(TokenType::Var) {
+            return self.var_declaration();
+        }
+        self.statement()
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt> {
+        let start = self.cursor - 1;
+        let loc = self.loc(self.cursor);
+        let name = self
+            .consume_next(TokenType::Identifier, "Expect class name.")?
+            .lexeme
+            .clone();
+
+        let superclass = if self.match_next(TokenType::Less) {
+            let super_idx = self.cursor;
+            let super_name = self
+                .consume_next(TokenType::Identifier, "Expect superclass name.")?
+                .lexeme
+                .clone();
+            if super_name == name {
+                self.report(
+                    super_idx,
+                    codes::SELF_INHERITANCE,
+                    "A class can't inherit from itself.",
+                );
+            }
+            Some(Var::new(
+                super_name,
+                self.loc(super_idx),
+                self.span(super_idx),
+            ))
+        } else {
+            None
+        };
+
+        self.consume_next(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        self.classes.push(superclass.is_some());
+        let methods = self.class_body();
+        self.classes.pop();
+
+        let methods = methods?;
+        Ok(Stmt::Class(Class {
+            name,
+            loc,
+            superclass,
+            methods,
+            span: self.span_from(start),
+        }))
+    }
+
+    fn class_body(&mut self) -> Result<Vec<Rc<Function>>> {
+        let mut methods = vec![];
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function("method")?.into());
+        }
+        self.consume_next(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(methods)
+    }
+
+    /// `kind` is only used to word error messages
+    fn function(&mut self, kind: &str) -> Result<Function> {
+        let start = self.cursor;
+        let loc = self.loc(self.cursor);
+        let name = self
+            .consume_next(TokenType::Identifier, &format!("Expect {kind} name."))?
+            .lexeme
+            .clone();
+        self.consume_next(
+            TokenType::LeftParen,
+            &format!("Expect '(' after {kind} name."),
+        )?;
+
+        let mut params = vec![];
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() == MAX_ARGS {
+                    self.report(
+                        self.cursor,
+                        codes::TOO_MANY_ARGUMENTS,
+                        &format!("Can't have more than {MAX_ARGS} parameters."),
+                    );
+                }
+                let name = self
+                    .consume_next(TokenType::Identifier, "Expect parameter name.")?
+                    .lexeme
+                    .clone();
+                let loc = self.loc(self.cursor - 1);
+                params.push(Param { name, loc });
+                if !self.match_next(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume_next(TokenType::RightParen, "Expect ')' after parameters.")?;
+
+        self.consume_next(
+            TokenType::LeftBrace,
+            &format!("Expect '{{' before {kind} body."),
+        )?;
+        let body = self.block()?;
+
+        Ok(Function {
+            name,
+            params,
+            body,
+            loc,
+            span: self.span_from(start),
+        })
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt> {
+        let start = self.cursor - 1;
+        let loc = self.loc(self.cursor);
+        let name = self
+            .consume_next(TokenType::Identifier, "Expect variable name.")?
+            .lexeme
+            .clone();
+
+        let initializer = if self.match_next(TokenType::Equal) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume_next(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+        Ok(Stmt::Var(VarDecl {
+            name,
+            initializer,
+            loc,
+            span: self.span_from(start),
+        }))
+    }
+
+    fn statement(&mut self) -> Result<Stmt> {
+        self.nested(Self::statement_inner)
+    }
+
+    fn statement_inner(&mut self) -> Result<Stmt> {
+        let start = self.cursor;
+        if self.match_next(TokenType::For) {
+            return self.for_statement();
+        }
+        if self.match_next(TokenType::If) {
+            return self.if_statement();
+        }
+        if self.match_next(TokenType::Print) {
+            return self.print_statement();
+        }
+        if self.check(TokenType::Return) {
+            return self.return_statement();
+        }
+        if self.match_next(TokenType::While) {
+            return self.while_statement();
+        }
+        if self.match_next(TokenType::LeftBrace) {
+            let stmts = self.block()?;
+            return Ok(Stmt::Block(stmts, self.span_from(start)));
+        }
+        self.expression_statement()
+    }
+
+    /// `for` has no node of its own, it is desugared into a [`While`] inside a block
+    fn for_statement(&mut self) -> Result<Stmt> {
+        let start = self.cursor - 1;
+        self.consume_next(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.match_next(TokenType::Semicolon) {
+            None
+        } else if self.match_next(TokenType::Var) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if self.check(TokenType::Semicolon) {
+            Expr::Literal(Lit::True, self.span(self.cursor))
+        } else {
+            self.expression()?
+        };
+        self.consume_next(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+
+        let increment = if self.check(TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume_next(TokenType::RightParen, "Expect ')' after for clauses.")?;
+
+        let mut body = self.statement()?;
+        // every node the loop is desugared into covers the whole loop
+        let span = self.span_from(start);
+        if let Some(increment) = increment {
+            let increment_span = increment.span();
+            body = Stmt::Block(
+                vec![body, Stmt::Expression(increment, increment_span)],
+                span,
+            );
+        }
+        body = Stmt::While(While {
+            condition,
+            body: body.into(),
+            span,
+        });
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(vec![initializer, body], span);
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt> {
+        let start = self.cursor - 1;
+        self.consume_next(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume_next(TokenType::RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch = self.statement()?.into();
+        // an `else` binds to the nearest `if`, which is the one we are in
+        let else_branch = if self.match_next(TokenType::Else) {
+            Some(self.statement()?.into())
+        } else {
+            None
+        };
+
+        Ok(Stmt::If(If {
+            condition,
+            then_branch,
+            else_branch,
+            span: self.span_from(start),
+        }))
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt> {
+        let start = self.cursor - 1;
+        self.consume_next(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume_next(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body = self.statement()?.into();
+
+        Ok(Stmt::While(While {
+            condition,
+            body,
+            span: self.span_from(start),
+        }))
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>> {
+        let mut stmts = vec![];
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if let Some(stmt) = self.declaration() {
+                stmts.push(stmt);
+            }
+        }
+        self.consume_next(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(stmts)
     }
 
-    pub fn parse(&mut self) -> Option<Expr> {
-        self.expression().ok()
+    fn print_statement(&mut self) -> Result<Stmt> {
+        let start = self.cursor - 1;
+        let expr = self.expression()?;
+        self.consume_next(TokenType::Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::Print(Print {
+            expr,
+            loc: self.loc(start),
+            span: self.span_from(start),
+        }))
     }
 
+    fn return_statement(&mut self) -> Result<Stmt> {
+        let start = self.cursor;
+        let loc = self.loc(self.cursor);
+        self.advance();
+
+        let value = if self.check(TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume_next(TokenType::Semicolon, "Expect ';' after return value.")?;
+
+        Ok(Stmt::Return(Return {
+            value,
+            loc,
+            span: self.span_from(start),
+        }))
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt> {
+        let start = self.cursor;
+        let expr = self.expression()?;
+        self.consume_next(TokenType::Semicolon, "Expect ';' after expression.")?;
+        Ok(Stmt::Expression(expr, self.span_from(start)))
+    }
+
+    /// Skips tokens until what is probably the start of the next statement
     fn synchronize(&mut self) {
         self.advance();
-        while let Some(curr_tag) = self.tokens.tags.get(self.cursor) {
-            if curr_tag == &TokenType::Semicolon
-                || curr_tag == &TokenType::Class
+        while !self.is_at_end() {
+            if self.tokens.tags[self.cursor - 1] == TokenType::Semicolon {
+                return;
+            }
+            let curr_tag = &self.tokens.tags[self.cursor];
+            if curr_tag == &TokenType::Class
                 || curr_tag == &TokenType::Fun
                 || curr_tag == &TokenType::Var
                 || curr_tag == &TokenType::For
@@ -40,40 +470,87 @@ impl Parser {
         }
     }
 
+    /// Consumes the current token and hands it back, staying put on the final Eof
     fn advance(&mut self) -> (&Token, &TokenType) {
-        if let (Some(tok), Some(tag)) = (
-            self.tokens.tokens.get(self.cursor),
-            self.tokens.tags.get(self.cursor),
-        ) {
+        let idx = self.cursor;
+        if !self.is_at_end() {
             self.cursor += 1;
-            return (tok, tag);
         }
-        (
-            self.tokens
-                .tokens
-                .get(self.cursor - 1)
-                .expect("should not yeat have reached end"),
-            self.tokens
-                .tags
-                .get(self.cursor - 1)
-                .expect("should not yeat have reached end"),
-        )
+        (&self.tokens.tokens[idx], &self.tokens.tags[idx])
+    }
+
+    /// The binary operator for the token at `idx`
+    fn operator(&self, idx: usize) -> Result<Ops> {
+        Ops::try_from(&self.tokens.tags[idx])
+            .map_err(|err| self.error(idx, codes::EXPECTED_EXPRESSION, &err.to_string()))
+    }
+
+    fn span(&self, idx: usize) -> Span {
+        self.tokens.spans[idx]
+    }
+
+    /// Span from the token at `start` through the last consumed token
+    fn span_from(&self, start: usize) -> Span {
+        let last = self.cursor.saturating_sub(1).max(start);
+        self.span(start).to(self.span(last))
+    }
+
+    fn loc(&self, idx: usize) -> Loc {
+        Loc {
+            line: self.tokens.line_nrs[idx],
+            col: self.tokens.cols[idx],
+            span: self.span(idx),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+
+        while self.match_next(TokenType::Or) {
+            self.link()?;
+            let right = self.and()?;
+            expr = Expr::Logical(Logical {
+                span: expr.span().to(right.span()),
+                left: expr.into(),
+                op: LogicalOp::Or,
+                right: right.into(),
+            });
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.equality()?;
+
+        while self.match_next(TokenType::And) {
+            self.link()?;
+            let right = self.equality()?;
+            expr = Expr::Logical(Logical {
+                span: expr.span().to(right.span()),
+                left: expr.into(),
+                op: LogicalOp::And,
+                right: right.into(),
+            });
+        }
+
+        Ok(expr)
     }
 
     fn equality(&mut self) -> Result<Expr> {
         let mut expr = self.comparison()?;
-        while self.cursor < self.tokens.tokens.len() {
-            if !self.tokens.tags[self.cursor].is_equality() {
-                break;
-            }
+        while self.tokens.tags[self.cursor].is_equality() {
             let curr = self.cursor;
             self.advance();
+            self.link()?;
             // we have an equality sign
             let right = self.comparison()?;
             expr = Expr::Binary(Bin {
+                span: expr.span().to(right.span()),
                 left: expr.into(),
-                op: (&self.tokens.tags[curr]).into(),
+                op: self.operator(curr)?,
                 right: right.into(),
+                loc: self.loc(curr),
             });
         }
 
@@ -83,17 +560,17 @@ impl Parser {
     fn comparison(&mut self) -> Result<Expr> {
         let mut expr = self.term()?;
 
-        while self.cursor < self.tokens.tokens.len() {
-            if !self.tokens.tags[self.cursor].is_comp() {
-                break;
-            }
+        while self.tokens.tags[self.cursor].is_comp() {
             let curr = self.cursor;
             self.advance();
+            self.link()?;
             let right = self.term()?;
             expr = Expr::Binary(Bin {
+                span: expr.span().to(right.span()),
                 left: expr.into(),
-                op: (&self.tokens.tags[curr]).into(),
+                op: self.operator(curr)?,
                 right: right.into(),
+                loc: self.loc(curr),
             });
         }
 
@@ -103,17 +580,17 @@ impl Parser {
     fn term(&mut self) -> Result<Expr> {
         let mut expr = self.factor()?;
 
-        while self.cursor < self.tokens.tokens.len() {
-            if !self.tokens.tags[self.cursor].is_term() {
-                break;
-            }
+        while self.tokens.tags[self.cursor].is_term() {
             let curr = self.cursor;
             self.advance();
+            self.link()?;
             let right = self.factor()?;
             expr = Expr::Binary(Bin {
+                span: expr.span().to(right.span()),
                 left: expr.into(),
-                op: (&self.tokens.tags[curr]).into(),
+                op: self.operator(curr)?,
                 right: right.into(),
+                loc: self.loc(curr),
             });
         }
 
@@ -123,87 +600,297 @@ impl Parser {
     fn factor(&mut self) -> Result<Expr> {
         let mut expr = self.unary()?;
 
-        while self.cursor < self.tokens.tokens.len() {
-            if !self.tokens.tags[self.cursor].is_factor() {
-                break;
-            }
+        while self.tokens.tags[self.cursor].is_factor() {
             let curr = self.cursor;
             self.advance();
+            self.link()?;
             let right = self.unary()?;
             expr = Expr::Binary(Bin {
+                span: expr.span().to(right.span()),
                 left: expr.into(),
-                op: (&self.tokens.tags[curr]).into(),
+                op: self.operator(curr)?,
                 right: right.into(),
+                loc: self.loc(curr),
             });
         }
         Ok(expr)
     }
 
     fn unary(&mut self) -> Result<Expr> {
-        while self.cursor < self.tokens.tokens.len() {
-            let curr_tag = self.tokens.tags[self.cursor];
-            if !curr_tag.is_unary() {
-                break;
+        if let Some(&curr_tag) = self.tokens.tags.get(self.cursor) {
+            if curr_tag.is_unary() {
+                let loc = self.loc(self.cursor);
+                let op_span = self.span(self.cursor);
+                self.advance();
+                let right = self.nested(Self::unary)?;
+                let span = op_span.to(right.span());
+                return Ok(Expr::Unary(if curr_tag == TokenType::Minus {
+                    Un::Minus(right.into(), loc, span)
+                } else {
+                    Un::Bang(right.into(), loc, span)
+                }));
             }
-            self.advance();
-            let right = self.unary()?;
-            return Ok(Expr::Unary(match curr_tag {
-                TokenType::Minus => Un::Minus(right.into()),
-                TokenType::Bang => Un::Bang(right.into()),
-                _ => panic!("invalid state: checked tok tag is ! or -"),
-            }));
         }
 
         // self.cursor here is whatever was determined to not be ! or -
-        return self.primary();
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<Expr> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.match_next(TokenType::LeftParen) {
+                self.link()?;
+                expr = self.finish_call(expr)?;
+            } else if self.match_next(TokenType::Dot) {
+                self.link()?;
+                let name_idx = self.cursor;
+                let name = self
+                    .consume_next(TokenType::Identifier, "Expect property name after '.'.")?
+                    .lexeme
+                    .clone();
+                expr = Expr::Get(Get {
+                    span: expr.span().to(self.span(name_idx)),
+                    object: expr.into(),
+                    name,
+                    loc: self.loc(name_idx),
+                });
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr> {
+        let mut args = vec![];
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if args.len() == MAX_ARGS {
+                    self.report(
+                        self.cursor,
+                        codes::TOO_MANY_ARGUMENTS,
+                        &format!("Can't have more than {MAX_ARGS} arguments."),
+                    );
+                }
+                args.push(self.expression()?);
+                if !self.match_next(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.cursor;
+        self.consume_next(TokenType::RightParen, "Expect ')' after arguments.")?;
+
+        Ok(Expr::Call(Call {
+            span: callee.span().to(self.span(paren)),
+            callee: callee.into(),
+            args,
+            loc: self.loc(paren),
+        }))
     }
 
     fn primary(&mut self) -> Result<Expr> {
+        let idx = self.cursor;
+        let span = self.span(idx);
         let curr = self.advance();
         match curr.1 {
-            TokenType::Nil => Ok(Expr::Literal(Lit::Nil)),
-            TokenType::False => Ok(Expr::Literal(Lit::False)),
-            TokenType::True => Ok(Expr::Literal(Lit::True)),
-            TokenType::Number => Ok(Expr::Literal(Lit::Num(curr.0.literal.clone()))),
-            TokenType::String => Ok(Expr::Literal(Lit::Str(curr.0.literal.clone()))),
+            TokenType::Nil => Ok(Expr::Literal(Lit::Nil, span)),
+            TokenType::False => Ok(Expr::Literal(Lit::False, span)),
+            TokenType::True => Ok(Expr::Literal(Lit::True, span)),
+            TokenType::Number => Ok(Expr::Literal(Lit::Num(curr.0.literal.clone()), span)),
+            TokenType::String => Ok(Expr::Literal(Lit::Str(curr.0.literal.clone()), span)),
+            TokenType::Interpolation => self.interpolation(idx),
+            TokenType::Identifier => {
+                let name = curr.0.lexeme.clone();
+                Ok(Expr::Variable(Var::new(name, self.loc(idx), span)))
+            }
+            TokenType::This => {
+                if self.classes.is_empty() {
+                    let err = "Can't use 'this' outside of a class.";
+                    return Err(self.error(idx, codes::THIS_OUTSIDE_CLASS, err));
+                }
+                Ok(Expr::This(Var::new(Rc::from("this"), self.loc(idx), span)))
+            }
+            TokenType::Super => {
+                let super_idx = self.cursor - 1;
+                match self.classes.last() {
+                    None => {
+                        let err = "Can't use 'super' outside of a class.";
+                        return Err(self.error(super_idx, codes::INVALID_SUPER, err));
+                    }
+                    Some(false) => {
+                        return Err(self.error(
+                            super_idx,
+                            codes::INVALID_SUPER,
+                            "Can't use 'super' in a class with no superclass.",
+                        ))
+                    }
+                    Some(true) => {}
+                }
+                self.consume_next(TokenType::Dot, "Expect '.' after 'super'.")?;
+                let method = self
+                    .consume_next(TokenType::Identifier, "Expect superclass method name.")?
+                    .lexeme
+                    .clone();
+                Ok(Expr::Super(Super {
+                    method,
+                    loc: self.loc(super_idx),
+                    depth: Cell::new(None),
+                    span: self.span_from(super_idx),
+                }))
+            }
             TokenType::LeftParen => {
                 let expr = self.expression()?;
-                self.consume_next(TokenType::RightParen, "expected \")\" to close expression")?;
-                return Ok(Expr::Grouping(expr.into()));
+                self.consume_next(TokenType::RightParen, "Expect ')' after expression.")?;
+                Ok(Expr::Grouping(expr.into(), self.span_from(idx)))
+            }
+            _ => {
+                // leave the offending token for `synchronize` to skip
+                self.cursor = idx;
+                Err(self.error(idx, codes::EXPECTED_EXPRESSION, "Expect expression."))
+            }
+        }
+    }
+
+    /// Parses the rest of an interpolated string whose first segment is at `start`
+    fn interpolation(&mut self, start: usize) -> Result<Expr> {
+        let mut parts = vec![];
+        let mut segment = start;
+        loop {
+            let literal = &self.tokens.tokens[segment].literal;
+            if !matches!(literal, Literal::String(s) if s.is_empty()) {
+                parts.push(Expr::Literal(Lit::Str(literal.clone()), self.span(segment)));
+            }
+            if self.tokens.tags[segment] == TokenType::InterpolationEnd {
+                break;
+            }
+
+            parts.push(self.expression()?);
+            segment = self.cursor;
+            if !self.match_next(TokenType::Interpolation)
+                && !self.match_next(TokenType::InterpolationEnd)
+            {
+                let err = "Expect '}' after interpolated expression.";
+                return Err(self.error(segment, codes::EXPECTED_RIGHT_BRACE, err));
             }
-            x => panic!("invalid primary sequence: {}", x),
         }
+
+        Ok(Expr::Interpolation(Interpolation {
+            parts,
+            span: self.span_from(start),
+        }))
     }
 
     fn expression(&mut self) -> Result<Expr> {
-        self.equality()
+        self.nested(Self::assignment)
     }
 
-    fn consume_next(&mut self, tok_type: TokenType, err_ctx: &str) -> Result<&Token> {
-        if self.cursor >= self.tokens.tokens.len() {
-            bail!("unexpected EOF");
+    fn assignment(&mut self) -> Result<Expr> {
+        let expr = self.or()?;
+
+        if self.check(TokenType::Equal) {
+            let equals = self.cursor;
+            self.advance();
+            let value = self.nested(Self::assignment)?;
+
+            match expr {
+                Expr::Variable(var) => {
+                    return Ok(Expr::Assign(Assign {
+                        name: var.name,
+                        span: var.span.to(value.span()),
+                        value: value.into(),
+                        loc: var.loc,
+                        depth: Cell::new(None),
+                    }))
+                }
+                Expr::Get(get) => {
+                    return Ok(Expr::Set(Set {
+                        object: get.object,
+                        name: get.name,
+                        span: get.span.to(value.span()),
+                        value: value.into(),
+                        loc: get.loc,
+                    }))
+                }
+                _ => {}
+            }
+
+            // the parser is not confused, so there is no need to synchronize
+            self.report(
+                equals,
+                codes::INVALID_ASSIGNMENT,
+                "Invalid assignment target.",
+            );
         }
 
-        if self.tokens.tags[self.cursor] == tok_type {
+        Ok(expr)
+    }
+
+    /// Runs `parse` one level deeper, failing once the code nests past [`MAX_DEPTH`].
+    /// Whatever `parse` adds with [`Parser::link`] is undone along with the level itself
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let depth = self.depth;
+        self.link()?;
+        let res = parse(self);
+        self.depth = depth;
+        res
+    }
+
+    /// Counts one more link of a chain like `a + b + c` or `a.b()`. The parser builds
+    /// those in a loop, but every later pass walks the resulting tree recursively
+    fn link(&mut self) -> Result<()> {
+        if self.depth == MAX_DEPTH {
+            let err = "Code is nested too deeply.";
+            return Err(self.error(self.cursor, codes::TOO_DEEPLY_NESTED, err));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn consume_next(&mut self, tok_type: TokenType, err_ctx: &str) -> Result<&Token> {
+        if self.check(tok_type) {
             return Ok(self.advance().0);
         }
-        let err_msg = err_msg!(
-            self.tokens.line_nrs[self.cursor],
-            err_ctx,
-            self.tokens.end_cols[self.cursor]
-        );
 
-        eprintln!("{err_msg}");
+        let code = match tok_type {
+            TokenType::RightParen => codes::EXPECTED_RIGHT_PAREN,
+            TokenType::LeftParen => codes::EXPECTED_LEFT_PAREN,
+            TokenType::LeftBrace => codes::EXPECTED_LEFT_BRACE,
+            TokenType::RightBrace => codes::EXPECTED_RIGHT_BRACE,
+            TokenType::Semicolon => codes::EXPECTED_SEMICOLON,
+            TokenType::Dot => codes::EXPECTED_DOT,
+            _ => codes::EXPECTED_NAME,
+        };
+        Err(self.error(self.cursor, code, err_ctx))
+    }
+
+    /// Builds an error of the kind `code` at the token at `idx`
+    fn error(&self, idx: usize, code: Code, err_ctx: &str) -> Diagnostic {
+        Diagnostic::error(self.loc(idx), code, err_ctx)
+    }
 
-        bail!(err_msg)
+    /// Records an error at the token at `idx` without unwinding the current parse
+    fn report(&mut self, idx: usize, code: Code, err_ctx: &str) {
+        let err = self.error(idx, code, err_ctx);
+        self.errors.push(err);
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{ast::printer::AstPrinter, scanner::Scanner};
+    use crate::{
+        ast::{printer::AstPrinter, Expr, Print, Stmt},
+        diagnostic::codes,
+        scanner::Scanner,
+        tokens::Span,
+    };
 
     use super::Parser;
+    use crate::runner::with_big_stack;
 
     #[test]
     fn test_parse_simple_equality() {
@@ -212,4 +899,370 @@ mod test {
         let parse_res = Parser::new(scan_res).parse();
         println!("{}", parse_res.unwrap().accept(&AstPrinter));
     }
+
+    #[test]
+    fn test_parse_statements() {
+        let code = "print 1 + 2;\n\"foo\";\nprint (3);";
+        let scan_res = Scanner::new(code).run();
+        let stmts = Parser::new(scan_res).parse_program().into_result().unwrap();
+        assert_eq!(stmts.len(), 3);
+        assert!(matches!(
+            stmts[0],
+            Stmt::Print(Print {
+                expr: Expr::Binary(_),
+                ..
+            })
+        ));
+        assert!(matches!(stmts[1], Stmt::Expression(Expr::Literal(..), _)));
+        assert!(matches!(
+            stmts[2],
+            Stmt::Print(Print {
+                expr: Expr::Grouping(..),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_statement_needs_semicolon() {
+        let scan_res = Scanner::new("print 1").run();
+        assert!(Parser::new(scan_res).parse_program().into_result().is_err());
+    }
+
+    #[test]
+    fn test_parse_declarations_and_blocks() {
+        let code = "var a = 1; var b; { a = b = 2; print a; }";
+        let scan_res = Scanner::new(code).run();
+        let stmts = Parser::new(scan_res).parse_program().into_result().unwrap();
+        assert_eq!(stmts.len(), 3);
+        assert!(matches!(&stmts[0], Stmt::Var(decl) if decl.initializer.is_some()));
+        assert!(matches!(&stmts[1], Stmt::Var(decl) if decl.initializer.is_none()));
+        let Stmt::Block(inner, _) = &stmts[2] else {
+            panic!("expected a block");
+        };
+        let Stmt::Expression(assign, _) = &inner[0] else {
+            panic!("expected an expression statement");
+        };
+        assert_eq!(assign.accept(&AstPrinter), "( = a ( = b 2 ) )");
+    }
+
+    #[test]
+    fn test_dangling_else_binds_to_nearest_if() {
+        let code = "if (true) if (false) print 1; else print 2;";
+        let scan_res = Scanner::new(code).run();
+        let stmts = Parser::new(scan_res).parse_program().into_result().unwrap();
+        assert_eq!(stmts.len(), 1);
+
+        let Stmt::If(outer) = &stmts[0] else {
+            panic!("expected an if statement");
+        };
+        assert!(outer.else_branch.is_none());
+        let Stmt::If(inner) = outer.then_branch.as_ref() else {
+            panic!("expected a nested if statement");
+        };
+        assert!(matches!(inner.else_branch.as_deref(), Some(Stmt::Print(_))));
+    }
+
+    #[test]
+    fn test_dangling_else_with_braces() {
+        let code = "if (true) { if (false) print 1; } else print 2;";
+        let scan_res = Scanner::new(code).run();
+        let stmts = Parser::new(scan_res).parse_program().into_result().unwrap();
+
+        let Stmt::If(outer) = &stmts[0] else {
+            panic!("expected an if statement");
+        };
+        assert!(matches!(outer.then_branch.as_ref(), Stmt::Block(_, _)));
+        assert!(outer.else_branch.is_some());
+    }
+
+    #[test]
+    fn test_deep_nesting_is_an_error() {
+        let errors = |code: &str| {
+            let scan_res = Scanner::new(code).run();
+            Parser::new(scan_res)
+                .parse_program()
+                .into_result()
+                .map(drop)
+                .map_err(|errs| errs[0].code)
+        };
+        let nest = |open: &str, close: &str, depth| {
+            format!("{}1{};", open.repeat(depth), close.repeat(depth))
+        };
+        let chain = |link: &str, len| format!("print a{};", link.repeat(len));
+        let too_deep = Err(codes::TOO_DEEPLY_NESTED);
+
+        with_big_stack(|| {
+            assert!(errors(&format!("print {}", nest("(", ")", 1000))).is_ok());
+            assert!(errors(&chain(" + 1", 10_000)).is_ok());
+
+            assert_eq!(
+                errors(&format!("print {}", nest("(", ")", 25_000))),
+                too_deep
+            );
+            assert_eq!(
+                errors(&format!("print {}", nest("-", "", 25_000))),
+                too_deep
+            );
+            assert_eq!(errors(&nest("a = ", "", 25_000)), too_deep);
+            assert_eq!(errors(&nest("{", "}", 25_000)), too_deep);
+            // chains are built in a loop, but nest just as deeply in the tree
+            for link in [
+                " + 1", " * 1", " < 1", " == 1", " and 1", " or 1", "()", ".b",
+            ] {
+                assert_eq!(errors(&chain(link, 25_000)), too_deep, "{link}");
+            }
+
+            // parsing carries on after the error
+            let code = format!("print {} print (;", nest("(", ")", 25_000));
+            let scan_res = Scanner::new(&code).run();
+            assert_eq!(Parser::new(scan_res).parse_program().errors.len(), 2);
+        });
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let code = r#"print "Hello ${name}, ${1 + 2}!";"#;
+        let scan_res = Scanner::new(code).run();
+        let stmts = Parser::new(scan_res).parse_program().into_result().unwrap();
+        let Stmt::Print(Print { expr, .. }) = &stmts[0] else {
+            panic!("expected a print statement");
+        };
+        assert_eq!(
+            expr.accept(&AstPrinter),
+            r#"( interp "Hello " name ", " ( + 1 2 ) "!" )"#
+        );
+        assert_eq!(expr.span(), Span::new(6, code.len() - 1));
+
+        let scan_res = Scanner::new(r#"print "${a b}";"#).run();
+        let errors = Parser::new(scan_res).parse_program().errors;
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0]
+            .to_string()
+            .ends_with("Expect '}' after interpolated expression."));
+    }
+
+    #[test]
+    fn test_logical_precedence() {
+        let code = "a or b and c == d;";
+        let scan_res = Scanner::new(code).run();
+        let stmts = Parser::new(scan_res).parse_program().into_result().unwrap();
+        let Stmt::Expression(expr, _) = &stmts[0] else {
+            panic!("expected an expression statement");
+        };
+        assert_eq!(expr.accept(&AstPrinter), "( or a ( and b ( == c d ) ) )");
+    }
+
+    #[test]
+    fn test_for_desugars_to_while() {
+        let code = "for (var i = 0; i < 3; i = i + 1) print i;";
+        let scan_res = Scanner::new(code).run();
+        let stmts = Parser::new(scan_res).parse_program().into_result().unwrap();
+
+        let Stmt::Block(outer, _) = &stmts[0] else {
+            panic!("expected the initializer block");
+        };
+        assert!(matches!(outer[0], Stmt::Var(_)));
+        let Stmt::While(while_stmt) = &outer[1] else {
+            panic!("expected a while loop");
+        };
+        assert_eq!(while_stmt.condition.accept(&AstPrinter), "( < i 3 )");
+        assert!(matches!(while_stmt.body.as_ref(), Stmt::Block(body, _) if body.len() == 2));
+    }
+
+    #[test]
+    fn test_parse_functions_and_calls() {
+        let code = "fun add(a, b) { return a + b; } add(1, 2)(3)();";
+        let scan_res = Scanner::new(code).run();
+        let stmts = Parser::new(scan_res).parse_program().into_result().unwrap();
+        assert_eq!(stmts.len(), 2);
+
+        let Stmt::Function(function) = &stmts[0] else {
+            panic!("expected a function declaration");
+        };
+        assert_eq!(&*function.name, "add");
+        assert_eq!(function.params.len(), 2);
+        assert!(matches!(function.body[0], Stmt::Return(_)));
+
+        let Stmt::Expression(call, _) = &stmts[1] else {
+            panic!("expected an expression statement");
+        };
+        assert_eq!(
+            call.accept(&AstPrinter),
+            "( call ( call ( call add 1 2 ) 3 ) )"
+        );
+    }
+
+    #[test]
+    fn test_too_many_arguments() {
+        let args = vec!["1"; 256].join(", ");
+        let scan_res = Scanner::new(&format!("f({args});")).run();
+        assert!(Parser::new(scan_res).parse_program().into_result().is_err());
+
+        let args = vec!["1"; 255].join(", ");
+        let scan_res = Scanner::new(&format!("f({args});")).run();
+        assert!(Parser::new(scan_res).parse_program().into_result().is_ok());
+    }
+
+    #[test]
+    fn test_parse_classes() {
+        let code = "class B < A { init(x) { this.x = x; } get() { return super.get(); } }";
+        let scan_res = Scanner::new(code).run();
+        let stmts = Parser::new(scan_res).parse_program().into_result().unwrap();
+
+        let Stmt::Class(class) = &stmts[0] else {
+            panic!("expected a class declaration");
+        };
+        assert_eq!(&*class.name, "B");
+        assert_eq!(class.superclass.as_ref().map(|s| &*s.name), Some("A"));
+        assert_eq!(class.methods.len(), 2);
+
+        let Stmt::Expression(set, _) = &class.methods[0].body[0] else {
+            panic!("expected an expression statement");
+        };
+        assert_eq!(set.accept(&AstPrinter), "( = ( . this x ) x )");
+        let Stmt::Return(ret) = &class.methods[1].body[0] else {
+            panic!("expected a return statement");
+        };
+        assert_eq!(
+            ret.value.as_ref().unwrap().accept(&AstPrinter),
+            "( call ( . super get ) )"
+        );
+    }
+
+    #[test]
+    fn test_class_static_errors() {
+        for code in [
+            "class A < A {}",
+            "print this;",
+            "fun f() { return this; }",
+            "print super.x;",
+            "class A { f() { return super.f(); } }",
+        ] {
+            let scan_res = Scanner::new(code).run();
+            assert!(
+                Parser::new(scan_res).parse_program().into_result().is_err(),
+                "{code}"
+            );
+        }
+
+        // functions nested in methods can still see `this`
+        let scan_res = Scanner::new("class A { f() { fun g() { return this; } } }").run();
+        assert!(Parser::new(scan_res).parse_program().into_result().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_assignment_target() {
+        let scan_res = Scanner::new("var a; var b; a + b = 3;").run();
+        assert!(Parser::new(scan_res).parse_program().into_result().is_err());
+
+        let scan_res = Scanner::new("{ var a = 1;").run();
+        assert!(Parser::new(scan_res).parse_program().into_result().is_err());
+    }
+
+    #[test]
+    fn test_reports_every_error() {
+        let code = "var = 1;\nprint 2;\nvar 3;\nfun () {}\nprint 4;";
+        let scan_res = Scanner::new(code).run();
+        let parsed = Parser::new(scan_res).parse_program();
+
+        let errors: Vec<_> = parsed.errors.iter().map(|err| err.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "[line: 1 column: 5] Error: Expect variable name.",
+                "[line: 3 column: 5] Error: Expect variable name.",
+                "[line: 4 column: 5] Error: Expect function name.",
+            ]
+        );
+        // the statements around the errors are still there
+        assert_eq!(parsed.stmts.len(), 2);
+        assert!(parsed
+            .stmts
+            .iter()
+            .all(|stmt| matches!(stmt, Stmt::Print(_))));
+    }
+
+    #[test]
+    fn test_recovers_inside_blocks() {
+        let scan_res = Scanner::new("{ print 1; var; print 2; } print 3;").run();
+        let parsed = Parser::new(scan_res).parse_program();
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(parsed.stmts.len(), 2);
+        assert!(matches!(&parsed.stmts[0], Stmt::Block(stmts, _) if stmts.len() == 2));
+    }
+
+    #[test]
+    fn test_expect_expression() {
+        for code in [")", "1 +", "(", "print", "*"] {
+            let scan_res = Scanner::new(code).run();
+            let errors = Parser::new(scan_res).parse().unwrap_err();
+            assert_eq!(errors.len(), 1, "{code}");
+            assert!(
+                errors[0].to_string().ends_with("Expect expression."),
+                "{code}"
+            );
+        }
+
+        // the offending token is skipped, the following statement survives
+        let scan_res = Scanner::new("print );\nprint 1;").run();
+        let parsed = Parser::new(scan_res).parse_program();
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(parsed.stmts.len(), 1);
+    }
+
+    #[test]
+    fn test_unclosed_grouping() {
+        let errors = Parser::new(Scanner::new("(1 + 2").run())
+            .parse()
+            .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, codes::EXPECTED_RIGHT_PAREN);
+        assert_eq!(errors[0].message, "Expect ')' after expression.");
+    }
+
+    #[test]
+    fn test_parse_needs_the_whole_input() {
+        let errors = Parser::new(Scanner::new("1 + 2 3").run())
+            .parse()
+            .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, codes::EXPECTED_END);
+        assert_eq!((errors[0].line, errors[0].col), (1, 7));
+
+        assert!(Parser::new(Scanner::new("print a").run()).parse().is_err());
+        assert!(Parser::new(Scanner::new("1 + 2 ").run()).parse().is_ok());
+    }
+
+    #[test]
+    fn test_parser_adds_missing_eof() {
+        let mut scan_res = Scanner::new("1 +").run();
+        scan_res.tokens.clear();
+        scan_res.tags.clear();
+        scan_res.line_nrs.clear();
+        scan_res.cols.clear();
+        scan_res.end_cols.clear();
+        assert!(Parser::new(scan_res).parse().is_err());
+    }
+
+    #[test]
+    fn test_spans_cover_whole_nodes() {
+        let code = "var x = -(1 + 2) * foo.bar(3);\nif (x) { print x; }";
+        let scan_res = Scanner::new(code).run();
+        let stmts = Parser::new(scan_res).parse_program().into_result().unwrap();
+        let text = |span: Span| &code[span.start..span.end];
+
+        assert_eq!(text(stmts[0].span()), "var x = -(1 + 2) * foo.bar(3);");
+        let Stmt::Var(decl) = &stmts[0] else {
+            panic!("expected a var declaration");
+        };
+        let Some(Expr::Binary(bin)) = &decl.initializer else {
+            panic!("expected a binary initializer");
+        };
+        assert_eq!(text(bin.span), "-(1 + 2) * foo.bar(3)");
+        assert_eq!(text(bin.left.span()), "-(1 + 2)");
+        assert_eq!(text(bin.right.span()), "foo.bar(3)");
+
+        assert_eq!(text(stmts[1].span()), "if (x) { print x; }");
+    }
 }
@@ -0,0 +1,397 @@
+use std::{cell::Cell, collections::HashMap, rc::Rc};
+
+use crate::{
+    ast::{
+        Assign, Bin, Call, Class, Expr, Function, Get, If, Interpolation, Lit, Logical, Print,
+        Return, Set, Stmt, StmtVisitorMut, Super, Un, Var, VarDecl, VisitorMut, While,
+    },
+    diagnostic::{codes, Code, Diagnostic},
+    tokens::{Loc, Span},
+};
+
+/// A variable declared in a local scope
+#[derive(Debug, Clone, Copy)]
+struct Local {
+    /// Whether its initializer has finished
+    defined: bool,
+    /// Where it was declared, empty for the implicit `this` and `super`
+    span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionKind {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+/// Static pass run between the parser and the interpreter.
+///
+/// Works out how many scopes separate every variable use from its declaration, and reports
+/// the mistakes that can be caught without running anything
+pub(crate) struct Resolver {
+    /// Local scopes only, globals are looked up dynamically
+    scopes: Vec<HashMap<Rc<str>, Local>>,
+    function: FunctionKind,
+    errors: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    pub(crate) fn new() -> Self {
+        Self {
+            scopes: vec![],
+            function: FunctionKind::None,
+            errors: vec![],
+        }
+    }
+
+    pub(crate) fn resolve(mut self, stmts: &[Stmt]) -> Result<(), Vec<Diagnostic>> {
+        self.resolve_stmts(stmts);
+        self.finish()
+    }
+
+    pub(crate) fn resolve_expr(mut self, expr: &Expr) -> Result<(), Vec<Diagnostic>> {
+        expr.accept_mut(&mut self);
+        self.finish()
+    }
+
+    fn finish(self) -> Result<(), Vec<Diagnostic>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn resolve_stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            stmt.accept_mut(self);
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Rc<str>, loc: Loc) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        let local = Local {
+            defined: false,
+            span: loc.span,
+        };
+        if let Some(previous) = scope.insert(name.clone(), local) {
+            let message = "Already a variable with this name in this scope.";
+            let err = Diagnostic::error(loc, codes::DUPLICATE_VARIABLE, message)
+                .with_label(previous.span, "previously declared here");
+            self.errors.push(err);
+        }
+    }
+
+    fn define(&mut self, name: &Rc<str>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope
+                .entry(name.clone())
+                .and_modify(|local| local.defined = true)
+                .or_insert(Local {
+                    defined: true,
+                    span: Span::default(),
+                });
+        }
+    }
+
+    fn resolve_local(&mut self, name: &str, depth: &Cell<Option<usize>>) {
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(name));
+        depth.set(found);
+    }
+
+    fn resolve_function(&mut self, function: &Function, kind: FunctionKind) {
+        let enclosing = self.function;
+        self.function = kind;
+
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(&param.name, param.loc);
+            self.define(&param.name);
+        }
+        self.resolve_stmts(&function.body);
+        self.end_scope();
+
+        self.function = enclosing;
+    }
+
+    fn error(&mut self, loc: Loc, code: Code, message: &str) {
+        self.errors.push(Diagnostic::error(loc, code, message));
+    }
+}
+
+impl VisitorMut for Resolver {
+    type Output = ();
+
+    fn visit_binary_mut(&mut self, expr: &Bin) -> Self::Output {
+        expr.left.accept_mut(self);
+        expr.right.accept_mut(self);
+    }
+
+    fn visit_unary_mut(&mut self, expr: &Un) -> Self::Output {
+        expr.inner().accept_mut(self);
+    }
+
+    fn visit_literal_mut(&mut self, _expr: &Lit) -> Self::Output {}
+
+    fn visit_grouping_mut(&mut self, expr: &Rc<Expr>) -> Self::Output {
+        expr.accept_mut(self);
+    }
+
+    fn visit_variable_mut(&mut self, expr: &Var) -> Self::Output {
+        let in_initializer = self
+            .scopes
+            .last()
+            .and_then(|scope| scope.get(&expr.name))
+            .is_some_and(|local| !local.defined);
+        if in_initializer {
+            self.error(
+                expr.loc,
+                codes::OWN_INITIALIZER,
+                "Can't read local variable in its own initializer.",
+            );
+        }
+        self.resolve_local(&expr.name, &expr.depth);
+    }
+
+    fn visit_assign_mut(&mut self, expr: &Assign) -> Self::Output {
+        expr.value.accept_mut(self);
+        self.resolve_local(&expr.name, &expr.depth);
+    }
+
+    fn visit_logical_mut(&mut self, expr: &Logical) -> Self::Output {
+        expr.left.accept_mut(self);
+        expr.right.accept_mut(self);
+    }
+
+    fn visit_call_mut(&mut self, expr: &Call) -> Self::Output {
+        expr.callee.accept_mut(self);
+        for arg in &expr.args {
+            arg.accept_mut(self);
+        }
+    }
+
+    fn visit_get_mut(&mut self, expr: &Get) -> Self::Output {
+        expr.object.accept_mut(self);
+    }
+
+    fn visit_set_mut(&mut self, expr: &Set) -> Self::Output {
+        expr.value.accept_mut(self);
+        expr.object.accept_mut(self);
+    }
+
+    fn visit_this_mut(&mut self, expr: &Var) -> Self::Output {
+        self.resolve_local(&expr.name, &expr.depth);
+    }
+
+    fn visit_super_mut(&mut self, expr: &Super) -> Self::Output {
+        self.resolve_local("super", &expr.depth);
+    }
+
+    fn visit_interpolation_mut(&mut self, expr: &Interpolation) -> Self::Output {
+        for part in &expr.parts {
+            part.accept_mut(self);
+        }
+    }
+}
+
+impl StmtVisitorMut for Resolver {
+    type Output = ();
+
+    fn visit_expression_stmt_mut(&mut self, expr: &Expr) -> Self::Output {
+        expr.accept_mut(self);
+    }
+
+    fn visit_print_stmt_mut(&mut self, stmt: &Print) -> Self::Output {
+        stmt.expr.accept_mut(self);
+    }
+
+    fn visit_var_stmt_mut(&mut self, stmt: &VarDecl) -> Self::Output {
+        self.declare(&stmt.name, stmt.loc);
+        if let Some(initializer) = &stmt.initializer {
+            initializer.accept_mut(self);
+        }
+        self.define(&stmt.name);
+    }
+
+    fn visit_block_stmt_mut(&mut self, stmts: &[Stmt]) -> Self::Output {
+        self.begin_scope();
+        self.resolve_stmts(stmts);
+        self.end_scope();
+    }
+
+    fn visit_if_stmt_mut(&mut self, stmt: &If) -> Self::Output {
+        stmt.condition.accept_mut(self);
+        stmt.then_branch.accept_mut(self);
+        if let Some(else_branch) = &stmt.else_branch {
+            else_branch.accept_mut(self);
+        }
+    }
+
+    fn visit_while_stmt_mut(&mut self, stmt: &While) -> Self::Output {
+        stmt.condition.accept_mut(self);
+        stmt.body.accept_mut(self);
+    }
+
+    fn visit_function_stmt_mut(&mut self, stmt: &Rc<Function>) -> Self::Output {
+        // defined straight away so the function can recurse
+        self.declare(&stmt.name, stmt.loc);
+        self.define(&stmt.name);
+        self.resolve_function(stmt, FunctionKind::Function);
+    }
+
+    fn visit_return_stmt_mut(&mut self, stmt: &Return) -> Self::Output {
+        if self.function == FunctionKind::None {
+            self.error(
+                stmt.loc,
+                codes::TOP_LEVEL_RETURN,
+                "Can't return from top-level code.",
+            );
+        }
+        if let Some(value) = &stmt.value {
+            if self.function == FunctionKind::Initializer {
+                let message = "Can't return a value from an initializer.";
+                let err = Diagnostic::error(stmt.loc, codes::RETURN_FROM_INITIALIZER, message)
+                    .with_note("'init' always returns the instance it was called on");
+                self.errors.push(err);
+            }
+            value.accept_mut(self);
+        }
+    }
+
+    fn visit_class_stmt_mut(&mut self, stmt: &Class) -> Self::Output {
+        self.declare(&stmt.name, stmt.loc);
+        self.define(&stmt.name);
+
+        if let Some(superclass) = &stmt.superclass {
+            self.visit_variable_mut(superclass);
+            self.begin_scope();
+            self.define(&Rc::from("super"));
+        }
+
+        self.begin_scope();
+        self.define(&Rc::from("this"));
+        for method in &stmt.methods {
+            let kind = if &*method.name == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+            self.resolve_function(method, kind);
+        }
+        self.end_scope();
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn resolve(code: &str) -> (Vec<Stmt>, Result<(), Vec<Diagnostic>>) {
+        let tokens = Scanner::new(code).run();
+        let stmts = Parser::new(tokens)
+            .parse_program()
+            .into_result()
+            .expect("should parse");
+        let res = Resolver::new().resolve(&stmts);
+        (stmts, res)
+    }
+
+    fn errors(code: &str) -> Vec<String> {
+        resolve(code)
+            .1
+            .expect_err("should not resolve")
+            .iter()
+            .map(|err| err.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_depths() {
+        let (stmts, res) = resolve("var a; { var b; { a; b; } }");
+        assert!(res.is_ok());
+
+        let Stmt::Block(outer, _) = &stmts[1] else {
+            panic!("expected a block");
+        };
+        let Stmt::Block(inner, _) = &outer[1] else {
+            panic!("expected a block");
+        };
+        let depth = |stmt: &Stmt| match stmt {
+            Stmt::Expression(Expr::Variable(var), _) => var.depth.get(),
+            _ => panic!("expected a variable"),
+        };
+        // globals are left to be looked up at runtime
+        assert_eq!(depth(&inner[0]), None);
+        assert_eq!(depth(&inner[1]), Some(1));
+    }
+
+    #[test]
+    fn test_own_initializer() {
+        assert_eq!(
+            errors("{ var a = 1; { var a = a; } }"),
+            vec!["[line: 1 column: 24] Error: Can't read local variable in its own initializer."]
+        );
+        // globals are fine, they are looked up dynamically
+        assert!(resolve("var a = 1; var a = a;").1.is_ok());
+    }
+
+    #[test]
+    fn test_redeclaration() {
+        assert_eq!(
+            errors("{ var a = 1;\nvar a = 2; }"),
+            vec!["[line: 2 column: 5] Error: Already a variable with this name in this scope."]
+        );
+        assert_eq!(errors("fun f(a, a) {}").len(), 1);
+
+        // the earlier declaration is pointed out too
+        let err = &resolve("{ var a; var a; }").1.unwrap_err()[0];
+        assert_eq!(err.span, Span::new(13, 14));
+        assert_eq!(err.labels[0].span, Span::new(6, 7));
+        assert_eq!(err.labels[0].message, "previously declared here");
+
+        // parameters are pointed at one by one, not at the function name
+        let err = &resolve("fun f(a, a) {}").1.unwrap_err()[0];
+        assert_eq!((err.col, err.span), (10, Span::new(9, 10)));
+        assert_eq!(err.labels[0].span, Span::new(6, 7));
+    }
+
+    #[test]
+    fn test_invalid_returns() {
+        assert_eq!(
+            errors("return 1;"),
+            vec!["[line: 1 column: 1] Error: Can't return from top-level code."]
+        );
+        let errs = resolve("class A { init() { return 1; } }").1.unwrap_err();
+        assert_eq!(errs[0].message, "Can't return a value from an initializer.");
+        assert_eq!((errs[0].line, errs[0].col), (1, 20));
+        assert_eq!(errs[0].notes.len(), 1);
+        // a bare return is allowed in an initializer
+        assert!(resolve("class A { init() { return; } }").1.is_ok());
+    }
+
+    #[test]
+    fn test_reports_every_error() {
+        assert_eq!(errors("return; { var a; var a; } return;").len(), 3);
+    }
+}
@@ -4,16 +4,51 @@ use std::{
     path::Path,
 };
 
+use super::{ErrorFormat, RunError};
+use crate::{ast::printer::AstPrinter, scanner::Scanner};
+
+/// Carries out a subcommand on a lox file, reporting any errors against its contents
 pub(crate) struct FileRunner<'path> {
     file: &'path Path,
+    error_format: ErrorFormat,
 }
 
 impl<'path> FileRunner<'path> {
-    pub(crate) fn new(file: &'path Path) -> Self {
-        Self { file }
+    pub(crate) fn new(file: &'path Path, error_format: ErrorFormat) -> Self {
+        Self { file, error_format }
     }
 
     pub(crate) fn run(self) -> Result<()> {
+        let contents = self.read()?;
+        self.finish(super::run(&contents, None), &contents)
+    }
+
+    /// Prints every token, then reports whatever the scanner couldn't make sense of
+    pub(crate) fn tokens(self) -> Result<()> {
+        let contents = self.read()?;
+        let tokens = Scanner::new(&contents).run();
+        print!("{tokens}");
+        let res = if tokens.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(RunError::Scan(tokens.errors))
+        };
+        self.finish(res, &contents)
+    }
+
+    /// Prints the syntax tree, before the resolver gets to reject anything
+    pub(crate) fn ast(self) -> Result<()> {
+        let contents = self.read()?;
+        let res = super::parse(&contents).map(|stmts| print!("{}", AstPrinter.print(&stmts)));
+        self.finish(res, &contents)
+    }
+
+    pub(crate) fn check(self) -> Result<()> {
+        let contents = self.read()?;
+        self.finish(super::check(&contents).map(drop), &contents)
+    }
+
+    fn read(&self) -> Result<String> {
         match self.file.extension() {
             Some(ext) => {
                 if ext != "lox" {
@@ -23,8 +58,20 @@ impl<'path> FileRunner<'path> {
             None => bail!("only .lox files may be run"),
         }
 
-        let file_contents = fs::read_to_string(self.file)?;
+        let contents = fs::read_to_string(self.file).map_err(|err| RunError::Io {
+            path: self.file.to_path_buf(),
+            err,
+        })?;
+        Ok(contents)
+    }
 
-        super::run(&file_contents, None)
+    /// Reports the diagnostics of a failed `res` against the file's `contents`
+    fn finish(&self, res: Result<(), RunError>, contents: &str) -> Result<()> {
+        let Err(err) = res else {
+            return Ok(());
+        };
+        let path = self.file.display().to_string();
+        super::report(&err, contents, &path, self.error_format);
+        Err(err.into())
     }
 }
@@ -3,18 +3,85 @@ mod repl;
 
 pub use clap::Parser;
 
-use anyhow::{bail, Context, Result};
+use clap::{error::ErrorKind, CommandFactory, Subcommand, ValueEnum};
+
+use anyhow::{anyhow, Result};
 use file_runner::FileRunner;
 use repl::{Repl, ReplCtx};
-use std::path::PathBuf;
+use std::{fmt::Display, panic, path::PathBuf, thread};
+
+use crate::{
+    ast::Stmt,
+    diagnostic::{codes, Code, Diagnostic, Renderer},
+    interpreter::Interpreter,
+    parser,
+    resolver::Resolver,
+    scanner::{Scanner, TokenInfo},
+    tokens::Loc,
+};
+
+/// Stack size of the threads lox code runs on. Parsing takes up to 30KB of stack per level
+/// of nesting in debug builds, so code nested as deep as the parser allows needs most of this
+pub(crate) const STACK_SIZE: usize = 1 << 30;
 
-use crate::scanner::Scanner;
+/// Runs `f` on a thread with [`STACK_SIZE`] bytes of stack, rather than on whatever is left
+/// of the caller's
+pub(crate) fn with_big_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)
+            .expect("failed to spawn a thread to run lox on")
+            .join()
+            .unwrap_or_else(|panic| panic::resume_unwind(panic))
+    })
+}
 
 #[derive(Parser, Debug)]
+#[command(name = "rlox")]
 pub struct InterpreterRunnerArgs {
-    /// The name of the file to run
-    #[arg(index = 1)]
-    pub file: Option<PathBuf>,
+    /// What to do, starts the repl when left out
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// How errors are reported on stderr
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Human)]
+    pub error_format: ErrorFormat,
+    /// Describe an error code, e.g. E0102, instead of running anything
+    #[arg(long, value_name = "CODE")]
+    pub explain: Option<String>,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Run a lox file
+    Run { file: PathBuf },
+    /// Print the tokens the scanner produces for a lox file
+    Tokens { file: PathBuf },
+    /// Print the syntax tree of a lox file
+    Ast { file: PathBuf },
+    /// Scan, parse and resolve a lox file without running it
+    Check { file: PathBuf },
+    /// Start an interactive session
+    Repl,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// Source snippets with the offending code underlined
+    Human,
+    /// One JSON object per line, for tools
+    Json,
+}
+
+impl InterpreterRunnerArgs {
+    /// Rejects what clap can't, `--explain` next to a subcommand it would silently ignore
+    pub fn validate(&self) -> Result<(), clap::Error> {
+        if self.explain.is_some() && self.command.is_some() {
+            let err = "the argument '--explain <CODE>' cannot be used with a subcommand";
+            return Err(Self::command().error(ErrorKind::ArgumentConflict, err));
+        }
+        Ok(())
+    }
 }
 
 pub struct InterpreterRunner {
@@ -27,27 +94,349 @@ impl InterpreterRunner {
     }
 
     pub fn run(self) -> Result<()> {
-        if let Some(file) = self.args.file {
-            FileRunner::new(&file).run()
-        } else {
-            Repl::new()?.run()
-        }
-    }
-}
-
-pub(crate) fn run(code: &str, ctx: Option<&mut ReplCtx>) -> Result<()> {
-    // let mut scanner = Scanner::new(code).into_iter();
-    //
-    // while let Some(token) = scanner.next() {
-    //     let token = token;
-    //     println!("{token}");
-    // }
-    //
-    // if scanner.has_errors() {
-    //     for error in scanner.errors() {
-    //         eprintln!("{error}");
-    //     }
-    //     bail!("Errors during lexing");
-    // }
-    Ok(())
+        with_big_stack(|| self.run_here())
+    }
+
+    fn run_here(self) -> Result<()> {
+        if let Err(err) = self.args.validate() {
+            err.exit();
+        }
+        if let Some(code) = &self.args.explain {
+            let code: Code = code.parse().map_err(|err: String| anyhow!(err))?;
+            let explanation =
+                codes::explain(code).ok_or_else(|| anyhow!("no error has the code {code}"))?;
+            println!(
+                "{code}: {}\n\n{}",
+                explanation.title, explanation.description
+            );
+            return Ok(());
+        }
+
+        let format = self.args.error_format;
+        let res = match self.args.command.unwrap_or(Command::Repl) {
+            Command::Run { file } => FileRunner::new(&file, format).run(),
+            Command::Tokens { file } => FileRunner::new(&file, format).tokens(),
+            Command::Ast { file } => FileRunner::new(&file, format).ast(),
+            Command::Check { file } => FileRunner::new(&file, format).check(),
+            Command::Repl => return Repl::new(format)?.run(),
+        };
+        let Err(err) = res else {
+            return Ok(());
+        };
+        let Some(run_err) = err.downcast_ref::<RunError>() else {
+            return Err(err);
+        };
+        // diagnostics were already shown against the file's contents, but a file that
+        // couldn't be read has none, so that error still has to be shown
+        if run_err.diagnostics().is_empty() {
+            eprintln!("Error: {err:#}");
+        }
+        std::process::exit(run_err.exit_code())
+    }
+}
+
+/// Why running a piece of lox code failed, so callers can tell the stages apart
+#[derive(Debug)]
+pub enum RunError {
+    /// The scanner rejected the source
+    Scan(Vec<Diagnostic>),
+    /// The parser rejected the token stream
+    Parse(Vec<Diagnostic>),
+    /// The program parsed but is not valid lox, e.g. a `return` outside of any function
+    Resolve(Vec<Diagnostic>),
+    /// The program failed while it was being evaluated
+    Runtime(Diagnostic),
+    /// The source file could not be read
+    Io { path: PathBuf, err: std::io::Error },
+}
+
+impl RunError {
+    /// Exit status to report for this error, following `sysexits.h`
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            // EX_DATAERR
+            RunError::Scan(_) | RunError::Parse(_) | RunError::Resolve(_) => 65,
+            // EX_SOFTWARE
+            RunError::Runtime(_) => 70,
+            // EX_NOINPUT
+            RunError::Io { .. } => 66,
+        }
+    }
+
+    /// Everything that was reported, in the order it was found
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            RunError::Scan(diagnostics)
+            | RunError::Parse(diagnostics)
+            | RunError::Resolve(diagnostics) => diagnostics,
+            RunError::Runtime(diagnostic) => std::slice::from_ref(diagnostic),
+            RunError::Io { .. } => &[],
+        }
+    }
+}
+
+impl Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunError::Scan(errors) => {
+                for error in errors {
+                    writeln!(f, "{error}")?;
+                }
+                write!(f, "Errors during lexing")
+            }
+            RunError::Parse(errors) => {
+                for error in errors {
+                    writeln!(f, "{error}")?;
+                }
+                write!(f, "Errors during parsing")
+            }
+            RunError::Resolve(errors) => {
+                for error in errors {
+                    writeln!(f, "{error}")?;
+                }
+                write!(f, "Errors during resolution")
+            }
+            RunError::Runtime(error) => write!(f, "{error}"),
+            RunError::Io { path, .. } => write!(f, "could not read {}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for RunError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RunError::Io { err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Writes every diagnostic of `err` to stderr, `path` names the `source` they were found in
+pub(crate) fn report(err: &RunError, source: &str, path: &str, format: ErrorFormat) {
+    let renderer = Renderer::for_stderr();
+    for diagnostic in err.diagnostics() {
+        match format {
+            ErrorFormat::Human => eprintln!("{}", renderer.render(diagnostic, source, path)),
+            ErrorFormat::Json => eprintln!("{}", diagnostic.to_json(source, path)),
+        }
+    }
+}
+
+fn scan(code: &str) -> Result<TokenInfo, RunError> {
+    let tokens = Scanner::new(code).run();
+    if !tokens.errors.is_empty() {
+        return Err(RunError::Scan(tokens.errors));
+    }
+    Ok(tokens)
+}
+
+/// Scans and parses `code`, without resolving the statements
+pub(crate) fn parse(code: &str) -> Result<Vec<Stmt>, RunError> {
+    parser::Parser::new(scan(code)?)
+        .parse_program()
+        .into_result()
+        .map_err(RunError::Parse)
+}
+
+/// Everything short of running `code`: scans, parses and resolves it
+pub(crate) fn check(code: &str) -> Result<Vec<Stmt>, RunError> {
+    let stmts = parse(code)?;
+    Resolver::new().resolve(&stmts).map_err(RunError::Resolve)?;
+    Ok(stmts)
+}
+
+pub(crate) fn run(code: &str, ctx: Option<&mut ReplCtx>) -> Result<(), RunError> {
+    let fresh;
+    let interpreter = match &ctx {
+        Some(ctx) => ctx.interpreter(),
+        None => {
+            fresh = Interpreter::new();
+            &fresh
+        }
+    };
+
+    // in the repl a lone expression is evaluated and its value echoed back, anything else
+    // goes to the statement parser, which also explains what is missing when neither fits
+    if ctx.is_some() {
+        let tokens = scan(code)?;
+        if let Some(&span) = tokens.spans.first() {
+            let loc = Loc {
+                line: tokens.line_nrs[0],
+                col: tokens.cols[0],
+                span,
+            };
+            if let Ok(expr) = parser::Parser::new(tokens).parse() {
+                Resolver::new()
+                    .resolve_expr(&expr)
+                    .map_err(RunError::Resolve)?;
+                let value = interpreter.interpret(&expr).map_err(RunError::Runtime)?;
+                return interpreter.print(&value, loc).map_err(RunError::Runtime);
+            }
+        }
+    }
+
+    let stmts = check(code)?;
+    interpreter.execute(&stmts).map_err(RunError::Runtime)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_errors_are_told_apart() {
+        let err = run(r#"-"abc";"#, None).unwrap_err();
+        assert!(matches!(err, RunError::Runtime(_)));
+        assert_eq!(err.exit_code(), 70);
+        assert_eq!(
+            err.to_string(),
+            "[line: 1 column: 1] Error: Operand must be a number."
+        );
+
+        let err = run("print (1 + 2;", None).unwrap_err();
+        assert!(matches!(err, RunError::Parse(_)));
+        assert_eq!(err.exit_code(), 65);
+
+        // malformed repl input is reported rather than taking the session down
+        let mut ctx = ReplCtx::default();
+        let err = run(")", Some(&mut ctx)).unwrap_err();
+        assert!(matches!(err, RunError::Parse(_)));
+
+        let err = run("return 1;", None).unwrap_err();
+        assert!(matches!(err, RunError::Resolve(_)));
+        assert_eq!(err.exit_code(), 65);
+    }
+
+    #[test]
+    fn test_error_format_flag() {
+        let args = InterpreterRunnerArgs::try_parse_from(["rlox", "run", "main.lox"]).unwrap();
+        assert_eq!(args.error_format, ErrorFormat::Human);
+
+        let args = InterpreterRunnerArgs::try_parse_from([
+            "rlox",
+            "run",
+            "main.lox",
+            "--error-format=json",
+        ])
+        .unwrap();
+        assert_eq!(args.error_format, ErrorFormat::Json);
+        let args = InterpreterRunnerArgs::try_parse_from([
+            "rlox",
+            "--error-format=json",
+            "check",
+            "a.lox",
+        ])
+        .unwrap();
+        assert_eq!(args.error_format, ErrorFormat::Json);
+        assert!(InterpreterRunnerArgs::try_parse_from(["rlox", "--error-format=xml"]).is_err());
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let parse = |args: &[&str]| InterpreterRunnerArgs::try_parse_from(args).unwrap().command;
+        let file = PathBuf::from("main.lox");
+        assert_eq!(parse(&["rlox"]), None);
+        assert_eq!(parse(&["rlox", "repl"]), Some(Command::Repl));
+        assert_eq!(
+            parse(&["rlox", "run", "main.lox"]),
+            Some(Command::Run { file: file.clone() })
+        );
+        assert_eq!(
+            parse(&["rlox", "tokens", "main.lox"]),
+            Some(Command::Tokens { file: file.clone() })
+        );
+        assert_eq!(
+            parse(&["rlox", "ast", "main.lox"]),
+            Some(Command::Ast { file: file.clone() })
+        );
+        assert_eq!(
+            parse(&["rlox", "check", "main.lox"]),
+            Some(Command::Check { file })
+        );
+        assert!(InterpreterRunnerArgs::try_parse_from(["rlox", "check"]).is_err());
+        assert!(InterpreterRunnerArgs::try_parse_from(["rlox", "main.lox"]).is_err());
+    }
+
+    #[test]
+    fn test_unreadable_files() {
+        let file = PathBuf::from("does/not/exist.lox");
+        let err = FileRunner::new(&file, ErrorFormat::Human)
+            .run()
+            .unwrap_err();
+        let err = err.downcast_ref::<RunError>().unwrap();
+        assert!(matches!(err, RunError::Io { .. }));
+        assert_eq!(err.exit_code(), 66);
+    }
+
+    #[test]
+    fn test_check_does_not_run() {
+        assert!(check("print nope;").is_ok());
+        assert!(matches!(check("return 1;"), Err(RunError::Resolve(_))));
+        assert!(matches!(parse("return 1;"), Ok(stmts) if stmts.len() == 1));
+    }
+
+    #[test]
+    fn test_repl_echoes_only_lone_expressions() {
+        let mut ctx = ReplCtx::default();
+        let mut repl = |code: &str| run(code, Some(&mut ctx));
+        assert!(repl("var a = 1").is_err());
+        assert!(repl("var a = 1;").is_ok());
+        assert!(repl("a + 2").is_ok());
+        assert!(repl("").is_ok());
+
+        // what is left over is reported by the statement parser, not echoed
+        let message = |res: Result<(), RunError>| res.unwrap_err().diagnostics()[0].message.clone();
+        assert_eq!(message(repl("1 + 2 3")), "Expect ';' after expression.");
+        assert_eq!(message(repl("print a")), "Expect ';' after value.");
+    }
+
+    #[test]
+    fn test_explain_flag() {
+        let args = InterpreterRunnerArgs::try_parse_from(["rlox", "--explain", "E0102"]).unwrap();
+        assert_eq!(args.explain.as_deref(), Some("E0102"));
+        assert!(args.command.is_none());
+
+        // explaining replaces running, so it can't be combined with a subcommand
+        let args = ["rlox", "--explain", "E0101", "run", "main.lox"];
+        let err = InterpreterRunnerArgs::try_parse_from(args)
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        let args = ["rlox", "run", "main.lox", "--explain", "E0101"];
+        assert!(InterpreterRunnerArgs::try_parse_from(args).is_err());
+        let args = ["rlox", "--explain", "E0101", "--error-format", "json"];
+        let args = InterpreterRunnerArgs::try_parse_from(args).unwrap();
+        assert!(args.validate().is_ok());
+
+        let args = InterpreterRunnerArgs::try_parse_from(["rlox", "--explain", "E0999"]).unwrap();
+        assert!(InterpreterRunner::new(args).run().is_err());
+    }
+
+    #[test]
+    fn test_deep_code_does_not_overflow() {
+        with_big_stack(|| {
+            assert!(check(&format!("print 1{};", " + 1".repeat(10_000))).is_ok());
+            assert!(check(&format!("print \"a\"{};", " + \"a\"".repeat(3000))).is_ok());
+
+            let ladder: String = (0..1000)
+                .map(|i| format!("if (x == {i}) print {i}; else "))
+                .collect();
+            assert!(check(&format!("var x = 999; {ladder} print nil;")).is_ok());
+
+            let err = check(&format!("print 1{};", " + 1".repeat(25_000))).unwrap_err();
+            assert_eq!(err.diagnostics()[0].code, codes::TOO_DEEPLY_NESTED);
+        });
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let code = |source: &str| run(source, None).unwrap_err().diagnostics()[0].code;
+        assert_eq!(code("print \"a"), codes::UNTERMINATED_STRING);
+        assert_eq!(code("print (1;"), codes::EXPECTED_RIGHT_PAREN);
+        assert_eq!(code("print 1"), codes::EXPECTED_SEMICOLON);
+        assert_eq!(code("return 1;"), codes::TOP_LEVEL_RETURN);
+        assert_eq!(code("print -\"a\";"), codes::WRONG_OPERAND_TYPE);
+        assert_eq!(code("print nope;"), codes::UNDEFINED_VARIABLE);
+    }
 }
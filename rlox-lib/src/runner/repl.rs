@@ -1,16 +1,21 @@
 use anyhow::{Context, Result};
 use rustyline::DefaultEditor;
 
+use super::ErrorFormat;
+use crate::interpreter::Interpreter;
+
 pub(crate) struct Repl {
     line_reader: DefaultEditor,
     ctx: ReplCtx,
+    error_format: ErrorFormat,
 }
 
 impl Repl {
-    pub(crate) fn new() -> Result<Self> {
+    pub(crate) fn new(error_format: ErrorFormat) -> Result<Self> {
         Ok(Self {
             line_reader: DefaultEditor::new()?,
             ctx: ReplCtx::default(),
+            error_format,
         })
     }
 
@@ -24,11 +29,20 @@ impl Repl {
                 return Ok(());
             }
             if let Err(e) = super::run(&rl, Some(&mut self.ctx)) {
-                println!("{e}");
+                super::report(&e, &rl, "<repl>", self.error_format);
             }
         }
     }
 }
 
+/// State kept between lines of the repl
 #[derive(Default)]
-pub struct ReplCtx {}
+pub struct ReplCtx {
+    interpreter: Interpreter,
+}
+
+impl ReplCtx {
+    pub(crate) fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+}
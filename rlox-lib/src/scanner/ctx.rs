@@ -1,11 +1,13 @@
-pub(crate) struct NeedsToken;
-pub(crate) struct Finished;
+use crate::diagnostic::Diagnostic;
 
 pub(crate) struct ScannerCtx {
     pub(crate) curr_line: usize,
     pub(crate) curr_col: usize,
-    pub(crate) errors: Vec<anyhow::Error>,
+    pub(crate) errors: Vec<Diagnostic>,
+    /// Byte offset of the current char
     pub(crate) cursor: usize,
+    /// Byte offset of the first char of the token being scanned
+    pub(crate) start: usize,
 }
 
 impl ScannerCtx {
@@ -14,6 +16,7 @@ impl ScannerCtx {
             curr_line: 1,
             curr_col: 0,
             cursor: 0,
+            start: 0,
             errors: vec![],
         }
     }
@@ -23,8 +26,9 @@ impl ScannerCtx {
         self.curr_col = 0;
     }
 
-    pub(crate) fn advance(&mut self) {
-        self.cursor += 1;
+    /// Moves past a char that is `len` bytes long
+    pub(crate) fn advance(&mut self, len: usize) {
+        self.cursor += len;
         self.curr_col += 1;
     }
 }
@@ -1,15 +1,32 @@
+//! Turns lox source code into [tokens](crate::tokens).
+//!
+//! Scanning never stops at an error, whatever couldn't be scanned is collected as a
+//! [`Diagnostic`] next to the tokens
+
+use std::fmt::Display;
+
 use crate::{
-    err_msg,
-    tokens::{Token, TokenType},
+    diagnostic::{codes, Code, Diagnostic},
+    tokens::{Literal, Loc, Span, SpannedToken, Token, TokenType},
 };
-use anyhow::{anyhow, Error};
 use ctx::ScannerCtx;
 
 pub(crate) mod ctx;
 
-pub(crate) struct Scanner<'code> {
+/// Turns source code into tokens, either all at once with [`Scanner::run`]
+/// or lazily by iterating over it
+pub struct Scanner<'code> {
     code: &'code str,
     ctx: ScannerCtx,
+    /// How many of `ctx.errors` the iterator has handed out so far
+    reported: usize,
+    /// A token held back until the errors hit while scanning it are handed out
+    pending: Option<SpannedToken>,
+    finished: bool,
+    /// One entry per `${` we are inside of, counting the `{` opened since
+    interpolations: Vec<usize>,
+    /// Byte offset, line and 0-based column of the last position looked up by [`Scanner::locate`]
+    located: (usize, usize, usize),
 }
 
 impl<'code> Scanner<'code> {
@@ -17,91 +34,212 @@ impl<'code> Scanner<'code> {
         Self {
             code,
             ctx: ScannerCtx::new(),
+            reported: 0,
+            pending: None,
+            finished: false,
+            interpolations: vec![],
+            located: (0, 1, 0),
         }
     }
 }
 
 /// Functions available in any state
 impl<'code> Scanner<'code> {
-    pub fn is_finished(&self) -> bool {
-        self.ctx.cursor == self.code.len()
-    }
-
-    pub fn curr_line(&self) -> usize {
-        self.ctx.curr_line.into()
-    }
-
-    pub fn curr_col(&self) -> usize {
-        self.ctx.curr_col
-    }
-
     pub fn has_errors(&self) -> bool {
         !self.ctx.errors.is_empty()
     }
 
-    pub fn errors(&self) -> &[anyhow::Error] {
+    pub fn errors(&self) -> &[Diagnostic] {
         &self.ctx.errors
     }
 
+    /// Steps over the current char and hands back the one after it
     fn advance(&mut self) -> Option<char> {
+        let curr = self.peek(0)?;
         let next = self.peek(1);
         if next.is_none() {
-            if self.ctx.cursor == self.code.len() - 1 {
-                self.ctx.cursor += 1
-            }
+            // step onto the end of the code, without counting a column for it
+            self.ctx.cursor += curr.len_utf8();
             return next;
         }
-        self.ctx.cursor += 1;
-        self.ctx.curr_col += 1;
+        self.ctx.advance(curr.len_utf8());
         next
     }
 
     fn rewind(&mut self) {
-        self.ctx.cursor -= 1;
-        self.ctx.curr_col -= 1;
+        if let Some(prev) = self.code[..self.ctx.cursor].chars().next_back() {
+            self.ctx.cursor -= prev.len_utf8();
+            // a one char token at the start of the last line sits on column 0
+            self.ctx.curr_col = self.ctx.curr_col.saturating_sub(1);
+        }
     }
 
+    /// The char `n` chars past the cursor. Only ever looks a char or two ahead,
+    /// so this stays cheap however far into the code we are
     fn peek(&self, n: usize) -> Option<char> {
-        self.code.chars().nth(self.ctx.cursor + n)
+        self.code[self.ctx.cursor..].chars().nth(n)
     }
 
     fn string_started(&mut self) -> Option<(TokenType, Token)> {
-        let start = (self.ctx.curr_line, self.ctx.curr_col);
+        self.string_part(TokenType::String)
+    }
+
+    /// Scans the rest of an interpolated string, from the `}` closing an interpolation
+    fn interpolation_ended(&mut self) -> Option<(TokenType, Token)> {
+        self.string_part(TokenType::InterpolationEnd)
+    }
+
+    /// Scans string contents from the char opening them up to the closing quote or the
+    /// next `${`. A string that ends at the quote is tagged `end_tag`
+    fn string_part(&mut self, end_tag: TokenType) -> Option<(TokenType, Token)> {
         let mut builder = String::new();
-        while let Some(curr_char) = self.advance() {
-            if curr_char == '"' {
-                return Some((TokenType::String, Token::new(&builder, true)));
+        self.advance();
+        while let Some(curr_char) = self.peek(0) {
+            match curr_char {
+                // the cursor is left on the closing quote, like on the last char of any token
+                '"' => return Some((end_tag, Token::new(&builder, true))),
+                '$' if self.peek(1) == Some('{') => {
+                    // the cursor is left on the `{`, the embedded expression starts after it
+                    self.advance();
+                    self.interpolations.push(0);
+                    return Some((TokenType::Interpolation, Token::new(&builder, true)));
+                }
+                '\\' => {
+                    if let Some(escaped) = self.escape() {
+                        builder.push(escaped);
+                    }
+                }
+                '\n' => {
+                    builder.push(curr_char);
+                    self.advance();
+                    self.ctx.newline();
+                }
+                _ => {
+                    builder.push(curr_char);
+                    self.advance();
+                }
             }
-            builder.push(curr_char);
         }
-        self.error(&format!(
-            "unterminated string starting at: {}:{}",
-            start.0, start.1
-        ));
+        let start = self.ctx.start;
+        self.error_at(start, codes::UNTERMINATED_STRING, "Unterminated string.");
         None
     }
 
+    /// Reads the escape sequence starting at the backslash under the cursor.
+    /// Invalid sequences are reported and the chars after the backslash are kept as they are
+    fn escape(&mut self) -> Option<char> {
+        let start = self.ctx.cursor;
+        self.advance();
+        let escaped = match self.peek(0)? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '"' => '"',
+            '\\' => '\\',
+            '$' => '$',
+            'u' => return self.unicode_escape(start),
+            other => {
+                let err = format!("Invalid escape sequence '\\{other}'.");
+                self.error_at(start, codes::INVALID_ESCAPE, &err);
+                return None;
+            }
+        };
+        self.advance();
+        Some(escaped)
+    }
+
+    /// Reads the `{XXXX}` part of a `\u{XXXX}` escape, the cursor starts on the `u`
+    fn unicode_escape(&mut self, start: usize) -> Option<char> {
+        self.advance();
+        if self.peek(0) != Some('{') {
+            self.error_at(start, codes::INVALID_ESCAPE, "Expect '{' after '\\u'.");
+            return None;
+        }
+        self.advance();
+
+        let mut hex = String::new();
+        while let Some(digit) = self.peek(0) {
+            if digit == '}' {
+                break;
+            }
+            if !digit.is_ascii_hexdigit() || hex.len() == 6 {
+                let err = "Expect 1 to 6 hex digits in '\\u{...}'.";
+                self.error_at(start, codes::INVALID_ESCAPE, err);
+                return None;
+            }
+            hex.push(digit);
+            self.advance();
+        }
+        // a missing '}' can only mean the string is unterminated, which gets reported anyway
+        self.peek(0)?;
+        self.advance();
+
+        let escaped = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32);
+        if escaped.is_none() {
+            let err = format!("Invalid unicode escape '\\u{{{hex}}}'.");
+            self.error_at(start, codes::INVALID_ESCAPE, &err);
+        }
+        escaped
+    }
+
+    /// Skips a `/* */` comment, which may nest. The cursor starts on its opening `/`
+    fn block_comment(&mut self) {
+        let start = self.ctx.cursor;
+        self.advance();
+        self.advance();
+
+        let mut depth = 1;
+        while let Some(curr_char) = self.peek(0) {
+            let next = self.peek(1);
+            if curr_char == '*' && next == Some('/') {
+                self.advance();
+                self.advance();
+                depth -= 1;
+                if depth == 0 {
+                    return;
+                }
+            } else if curr_char == '/' && next == Some('*') {
+                self.advance();
+                self.advance();
+                depth += 1;
+            } else if curr_char == '\n' {
+                self.advance();
+                self.ctx.newline();
+            } else {
+                self.advance();
+            }
+        }
+
+        self.error_at(
+            start,
+            codes::UNTERMINATED_COMMENT,
+            "Unterminated block comment.",
+        );
+    }
+
     fn is_alpha(c: char) -> bool {
         c.is_ascii_alphabetic() || c == '_'
     }
 
+    fn is_alphanumeric(c: char) -> bool {
+        Self::is_alpha(c) || c.is_ascii_digit()
+    }
+
     fn want_number(&mut self) -> (TokenType, Token) {
         let mut builder = String::new();
+        let radix =
+            self.peek(0) == Some('0') && matches!(self.peek(1), Some('x' | 'X' | 'b' | 'B'));
+        // take in anything that could belong to the number, mistakes are caught by `parse_number`
         while let Some(next) = self.peek(0) {
-            if next.is_digit(10) {
+            let fraction = next == '.'
+                && !radix
+                && !builder.contains(['.', 'e', 'E'])
+                && self.peek(1).is_some_and(|c| c.is_ascii_digit());
+            let exponent_sign =
+                matches!(next, '+' | '-') && !radix && builder.ends_with(['e', 'E']);
+            if next.is_ascii_alphanumeric() || next == '_' || fraction || exponent_sign {
                 builder.push(next);
                 self.advance();
-            } else if next == '.' {
-                if let Some(next_next) = self.peek(1) {
-                    if next_next.is_digit(10) {
-                        builder.push(next);
-                        self.advance();
-                    } else {
-                        break;
-                    }
-                } else {
-                    break;
-                }
             } else {
                 break;
             }
@@ -109,13 +247,76 @@ impl<'code> Scanner<'code> {
 
         // rewind by 1
         self.rewind();
-        (TokenType::Number, Token::new(&builder, true))
+        let value = Self::parse_number(&builder).unwrap_or_else(|err| {
+            self.error(codes::MALFORMED_NUMBER, &err);
+            0.0
+        });
+        (TokenType::Number, Token::number(&builder, value))
+    }
+
+    /// Works out the value of a number literal like `1_000`, `0xFF`, `0b1010` or `1.5e-9`
+    fn parse_number(lexeme: &str) -> Result<f64, String> {
+        if let Some(prefix @ ("0x" | "0X" | "0b" | "0B")) = lexeme.get(..2) {
+            let radix = if prefix.ends_with(['x', 'X']) { 16 } else { 2 };
+            let digits = Self::digits(lexeme, &lexeme[2..], radix)?;
+            if digits.is_empty() {
+                return Err(format!("Expect digits after '{prefix}'."));
+            }
+            return Ok(digits.chars().fold(0.0, |value, digit| {
+                let digit = digit.to_digit(radix).expect("digits were checked");
+                value * f64::from(radix) + f64::from(digit)
+            }));
+        }
+
+        let (mantissa, exponent) = match lexeme.find(['e', 'E']) {
+            Some(idx) => (&lexeme[..idx], Some(&lexeme[idx + 1..])),
+            None => (lexeme, None),
+        };
+        let mut cleaned = String::new();
+        match mantissa.split_once('.') {
+            Some((int, fraction)) => {
+                cleaned.push_str(&Self::digits(lexeme, int, 10)?);
+                cleaned.push('.');
+                cleaned.push_str(&Self::digits(lexeme, fraction, 10)?);
+            }
+            None => cleaned.push_str(&Self::digits(lexeme, mantissa, 10)?),
+        }
+        if let Some(exponent) = exponent {
+            let unsigned = exponent.trim_start_matches(['+', '-']);
+            let digits = Self::digits(lexeme, unsigned, 10)?;
+            if digits.is_empty() {
+                return Err(format!("Expect digits in the exponent of '{lexeme}'."));
+            }
+            cleaned.push('e');
+            cleaned.push_str(&exponent[..exponent.len() - unsigned.len()]);
+            cleaned.push_str(&digits);
+        }
+        cleaned
+            .parse()
+            .map_err(|_| format!("Invalid number literal '{lexeme}'."))
+    }
+
+    /// Checks a run of digits in `radix` and strips the `_` separators out of it
+    fn digits(lexeme: &str, run: &str, radix: u32) -> Result<String, String> {
+        if run.starts_with('_') || run.ends_with('_') || run.contains("__") {
+            return Err(format!("'_' must sit between digits in '{lexeme}'."));
+        }
+        run.chars()
+            .filter(|&c| c != '_')
+            .map(|c| {
+                if c.is_digit(radix) {
+                    Ok(c)
+                } else {
+                    Err(format!("Invalid digit '{c}' in number literal '{lexeme}'."))
+                }
+            })
+            .collect()
     }
 
     fn want_ident(&mut self) -> (TokenType, Token) {
         let mut builder = String::new();
         while let Some(next) = self.peek(0) {
-            if Self::is_alpha(next) {
+            if Self::is_alphanumeric(next) {
                 builder.push(next);
                 self.advance();
             } else {
@@ -134,6 +335,7 @@ impl<'code> Scanner<'code> {
 
     fn get_next_token(&mut self) -> Option<(TokenType, Token)> {
         while let Some(curr_char) = self.peek(0) {
+            self.ctx.start = self.ctx.cursor;
             match curr_char {
                 '"' => return self.string_started(),
                 '(' => {
@@ -148,17 +350,27 @@ impl<'code> Scanner<'code> {
                         Token::new(&format!("{curr_char}"), false),
                     ))
                 }
+                '}' if self.interpolations.last() == Some(&0) => {
+                    self.interpolations.pop();
+                    return self.interpolation_ended();
+                }
                 '{' => {
+                    if let Some(depth) = self.interpolations.last_mut() {
+                        *depth += 1;
+                    }
                     return Some((
                         TokenType::LeftBrace,
                         Token::new(&format!("{curr_char}"), false),
-                    ))
+                    ));
                 }
                 '}' => {
+                    if let Some(depth) = self.interpolations.last_mut() {
+                        *depth -= 1;
+                    }
                     return Some((
                         TokenType::RightBrace,
                         Token::new(&format!("{curr_char}"), false),
-                    ))
+                    ));
                 }
                 ',' => return Some((TokenType::Comma, Token::new(&format!("{curr_char}"), false))),
                 '.' => return Some((TokenType::Dot, Token::new(&format!("{curr_char}"), false))),
@@ -171,13 +383,13 @@ impl<'code> Scanner<'code> {
                     ))
                 }
                 '*' => return Some((TokenType::Star, Token::new(&format!("{curr_char}"), false))),
-                num if num >= '0' && num <= '9' => return Some(self.want_number()),
+                '0'..='9' => return Some(self.want_number()),
                 '!' => {
                     let Some(next) = self.peek(1) else {
                         return Some((TokenType::Bang, Token::new(&format!("{curr_char}"), false)));
                     };
                     if next == '=' {
-                        let next = self.advance().unwrap();
+                        self.advance();
                         return Some((
                             TokenType::BangEqual,
                             Token::new(&format!("{curr_char}{next}"), false),
@@ -194,7 +406,7 @@ impl<'code> Scanner<'code> {
                         ));
                     };
                     if next == '=' {
-                        let next = self.advance().unwrap();
+                        self.advance();
                         return Some((
                             TokenType::EqualEqual,
                             Token::new(&format!("{curr_char}{next}"), false),
@@ -207,14 +419,14 @@ impl<'code> Scanner<'code> {
                     }
                 }
                 '>' => {
-                    let Some(next) = self.peek(0) else {
+                    let Some(next) = self.peek(1) else {
                         return Some((
                             TokenType::Greater,
                             Token::new(&format!("{curr_char}"), false),
                         ));
                     };
                     if next == '=' {
-                        let next = self.advance().unwrap();
+                        self.advance();
                         return Some((
                             TokenType::GreaterEqual,
                             Token::new(&format!("{curr_char}{next}"), false),
@@ -231,7 +443,7 @@ impl<'code> Scanner<'code> {
                         return Some((TokenType::Less, Token::new(&format!("{curr_char}"), false)));
                     };
                     if next == '=' {
-                        let next = self.advance().unwrap();
+                        self.advance();
                         return Some((
                             TokenType::LessEqual,
                             Token::new(&format!("{curr_char}{next}"), false),
@@ -251,6 +463,8 @@ impl<'code> Scanner<'code> {
                                     break;
                                 }
                             }
+                        } else if next == '*' {
+                            self.block_comment();
                         } else {
                             return Some((
                                 TokenType::Slash,
@@ -268,73 +482,191 @@ impl<'code> Scanner<'code> {
                     self.advance();
                 }
                 '\n' => {
+                    self.advance();
                     self.ctx.newline();
                 }
-                _ => {
+                c if Self::is_alpha(c) => {
                     return Some(self.want_ident());
                 }
+                _ => {
+                    // skip it and keep scanning, to report as many errors as we can
+                    let err = format!("Unexpected character '{curr_char}'.");
+                    self.error(codes::UNEXPECTED_CHARACTER, &err);
+                    self.advance();
+                }
             }
         }
 
         Some((TokenType::Eof, Token::new("", false)))
     }
 
-    fn error(&mut self, err: &str) {
-        self.ctx.errors.push(anyhow!(err_msg!(
-            self.ctx.curr_line,
-            self.ctx.curr_col,
-            err
-        )));
+    /// Reports `err` at the cursor, covering the token scanned so far
+    fn error(&mut self, code: Code, err: &str) {
+        self.error_at(self.ctx.start, code, err);
     }
 
-    pub(crate) fn run(mut self) -> TokenInfo {
+    /// Reports `err` covering the code from byte `start` through the cursor
+    fn error_at(&mut self, start: usize, code: Code, err: &str) {
+        let (line, col) = self.locate(start);
+        let end = self.ctx.cursor + self.peek(0).map_or(0, char::len_utf8);
+        let span = Span::new(start, end.max(start));
+        self.ctx
+            .errors
+            .push(Diagnostic::error(Loc { line, col, span }, code, err));
+    }
+
+    /// Scans the next token, the last one is always an Eof
+    fn next_token(&mut self) -> SpannedToken {
+        // an unterminated string ends the input early, that still needs an Eof
+        let (tag, token) = self
+            .get_next_token()
+            .unwrap_or_else(|| (TokenType::Eof, Token::new("", false)));
+        let span = if tag == TokenType::Eof {
+            Span::new(self.code.len(), self.code.len())
+        } else {
+            // the cursor is left on the last char of the token
+            let last_len = self.peek(0).map_or(0, char::len_utf8);
+            Span::new(self.ctx.start, self.ctx.cursor + last_len)
+        };
+        // like rendered diagnostics, the end of input sits right after the last token
+        let start = if tag == TokenType::Eof {
+            self.code.trim_end().len()
+        } else {
+            span.start
+        };
+        let (line, col) = self.locate(start);
+        let spanned = SpannedToken {
+            tag,
+            token,
+            line,
+            col,
+            end_col: self.ctx.curr_col,
+            span,
+        };
+        if tag == TokenType::Eof {
+            self.finished = true;
+        } else {
+            self.advance();
+        }
+        spanned
+    }
+
+    /// The line and 1-based column of byte `offset`. Lookups mostly move forward,
+    /// so only the code since the last one is walked
+    fn locate(&mut self, offset: usize) -> (usize, usize) {
+        let (mut from, mut line, mut col) = self.located;
+        if offset < from {
+            (from, line, col) = (0, 1, 0);
+        }
+        for c in self.code[from..offset].chars() {
+            if c == '\n' {
+                line += 1;
+                col = 0;
+            } else {
+                col += 1;
+            }
+        }
+        self.located = (offset, line, col);
+        (line, col + 1)
+    }
+
+    /// Scans all of the code at once
+    pub fn run(mut self) -> TokenInfo {
         let mut tokens = vec![];
         let mut tags = vec![];
         let mut line_nrs = vec![];
+        let mut cols = vec![];
         let mut end_cols = vec![];
-        loop {
-            let Some((tag, tok)) = self.get_next_token() else {
-                debug_assert!(tags[tags.len() - 1] == TokenType::Eof);
-                return TokenInfo {
-                    tokens,
-                    tags,
-                    line_nrs,
-                    end_cols,
-                    errors: self.ctx.errors,
-                };
-            };
-            tags.push(tag);
-            tokens.push(tok);
-            line_nrs.push(self.ctx.curr_line);
-            end_cols.push(self.ctx.curr_col);
-            if tag == TokenType::Eof {
-                break;
-            }
-            self.advance();
+        let mut spans = vec![];
+        // errors are collected from the ctx below, so skip them here
+        for spanned in self.by_ref().flatten() {
+            tags.push(spanned.tag);
+            tokens.push(spanned.token);
+            line_nrs.push(spanned.line);
+            cols.push(spanned.col);
+            end_cols.push(spanned.end_col);
+            spans.push(spanned.span);
         }
-        debug_assert!(tags[tags.len() - 1] == TokenType::Eof);
         TokenInfo {
             tokens,
             tags,
             line_nrs,
+            cols,
             end_cols,
+            spans,
             errors: self.ctx.errors,
         }
     }
 }
 
+/// Hands out tokens as they are scanned, with any errors in the order they were hit.
+/// Ends after the Eof token
+impl Iterator for Scanner<'_> {
+    type Item = Result<SpannedToken, Diagnostic>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.ctx.errors.get(self.reported) {
+            self.reported += 1;
+            return Some(Err(err.clone()));
+        }
+        if let Some(spanned) = self.pending.take() {
+            return Some(Ok(spanned));
+        }
+        if self.finished {
+            return None;
+        }
+
+        let spanned = self.next_token();
+        if self.reported < self.ctx.errors.len() {
+            self.pending = Some(spanned);
+            return self.next();
+        }
+        Some(Ok(spanned))
+    }
+}
+
+/// Everything the scanner found, the vecs hold one entry per token, ending with Eof
 #[derive(Debug)]
-pub(crate) struct TokenInfo {
-    pub(crate) tokens: Vec<Token>,
-    pub(crate) tags: Vec<TokenType>,
-    pub(crate) line_nrs: Vec<usize>,
-    pub(crate) end_cols: Vec<usize>,
-    pub(crate) errors: Vec<Error>,
+pub struct TokenInfo {
+    pub tokens: Vec<Token>,
+    pub tags: Vec<TokenType>,
+    /// Line each token starts on
+    pub line_nrs: Vec<usize>,
+    /// 1-based column each token starts at
+    pub cols: Vec<usize>,
+    /// Column each token ends at
+    pub end_cols: Vec<usize>,
+    /// Byte range of each token in the source
+    pub spans: Vec<Span>,
+    pub errors: Vec<Diagnostic>,
+}
+
+/// One token per line: its line, byte span, type and lexeme or literal value
+impl Display for TokenInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, token) in self.tokens.iter().enumerate() {
+            let span = self.spans[idx];
+            let span = format!("{}..{}", span.start, span.end);
+            let text = match &token.literal {
+                Literal::None => token.lexeme.escape_debug().to_string(),
+                literal => literal.to_string().escape_debug().to_string(),
+            };
+            let line = format!(
+                "{:>4} {span:<11} {:<16} {text}",
+                self.line_nrs[idx],
+                // `TokenType` ignores the width, pad its name instead
+                self.tags[idx].to_string()
+            );
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::{rc::Rc, time::Instant};
 
     #[test]
     fn test_simple_symbols() {
@@ -383,15 +715,38 @@ mod test {
                 TokenType::Eof,
             ],
             line_nrs: vec![2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3],
+            cols: vec![1, 2, 4, 5, 6, 7, 1, 2, 3, 4, 5, 6, 7, 8, 10, 13, 15, 30],
             end_cols: vec![0, 1, 3, 4, 5, 6, 0, 1, 2, 3, 4, 5, 6, 7, 10, 13, 15, 28],
+            spans: vec![
+                Span::new(21, 22),
+                Span::new(22, 23),
+                Span::new(24, 25),
+                Span::new(25, 26),
+                Span::new(26, 27),
+                Span::new(27, 28),
+                Span::new(47, 48),
+                Span::new(48, 49),
+                Span::new(49, 50),
+                Span::new(50, 51),
+                Span::new(51, 52),
+                Span::new(52, 53),
+                Span::new(53, 54),
+                Span::new(54, 55),
+                Span::new(56, 58),
+                Span::new(59, 61),
+                Span::new(61, 63),
+                Span::new(76, 76),
+            ],
             errors: vec![],
         };
         let scan_res = Scanner::new(code).run();
 
         assert_eq!(scan_res.tokens, exp.tokens);
         assert_eq!(scan_res.tags, exp.tags);
+        assert_eq!(scan_res.cols, exp.cols);
         assert_eq!(scan_res.end_cols, exp.end_cols);
         assert_eq!(scan_res.line_nrs, exp.line_nrs);
+        assert_eq!(scan_res.spans, exp.spans);
         assert!(scan_res.errors.is_empty());
     }
 
@@ -400,9 +755,9 @@ mod test {
         let code = r#"123 123.456 0.123"#;
         let exp = TokenInfo {
             tokens: vec![
-                Token::new("123", true),
-                Token::new("123.456", true),
-                Token::new("0.123", true),
+                Token::number("123", 123.0),
+                Token::number("123.456", 123.456),
+                Token::number("0.123", 0.123),
                 Token::new("", false),
             ],
             tags: vec![
@@ -412,15 +767,24 @@ mod test {
                 TokenType::Eof,
             ],
             line_nrs: vec![1, 1, 1, 1],
+            cols: vec![1, 5, 13, 18],
             end_cols: vec![2, 10, 15, 15],
+            spans: vec![
+                Span::new(0, 3),
+                Span::new(4, 11),
+                Span::new(12, 17),
+                Span::new(17, 17),
+            ],
             errors: vec![],
         };
         let scan_res = Scanner::new(code).run();
 
         assert_eq!(scan_res.tokens, exp.tokens);
         assert_eq!(scan_res.tags, exp.tags);
+        assert_eq!(scan_res.cols, exp.cols);
         assert_eq!(scan_res.end_cols, exp.end_cols);
         assert_eq!(scan_res.line_nrs, exp.line_nrs);
+        assert_eq!(scan_res.spans, exp.spans);
         assert!(scan_res.errors.is_empty());
     }
 
@@ -453,15 +817,383 @@ mod test {
                 TokenType::Eof,
             ],
             line_nrs: vec![1; 10],
+            cols: vec![1, 5, 9, 13, 16, 20, 24, 29, 32, 35],
             end_cols: vec![2, 6, 10, 13, 17, 21, 26, 29, 32, 32],
+            spans: vec![
+                Span::new(0, 3),
+                Span::new(4, 7),
+                Span::new(8, 11),
+                Span::new(12, 14),
+                Span::new(15, 18),
+                Span::new(19, 22),
+                Span::new(23, 27),
+                Span::new(28, 30),
+                Span::new(31, 34),
+                Span::new(34, 34),
+            ],
             errors: vec![],
         };
         let scan_res = Scanner::new(code).run();
 
         assert_eq!(scan_res.tokens, exp.tokens);
         assert_eq!(scan_res.tags, exp.tags);
+        assert_eq!(scan_res.cols, exp.cols);
         assert_eq!(scan_res.end_cols, exp.end_cols);
         assert_eq!(scan_res.line_nrs, exp.line_nrs);
+        assert_eq!(scan_res.spans, exp.spans);
+        assert!(scan_res.errors.is_empty());
+    }
+
+    #[test]
+    fn test_idents_with_digits() {
+        let scan_res = Scanner::new("var x1 = 10; a_2b3 _9").run();
+        let lexemes: Vec<_> = scan_res.tokens.iter().map(|tok| &*tok.lexeme).collect();
+        assert_eq!(lexemes, ["var", "x1", "=", "10", ";", "a_2b3", "_9", ""]);
+        assert_eq!(scan_res.tags[1], TokenType::Identifier);
+        assert_eq!(scan_res.tags[5], TokenType::Identifier);
+        assert_eq!(scan_res.tags[6], TokenType::Identifier);
+        assert!(scan_res.errors.is_empty());
+    }
+
+    #[test]
+    fn test_newlines() {
+        let code = "1\n\n  2\n";
+        let scan_res = Scanner::new(code).run();
+
+        assert_eq!(
+            scan_res.tags,
+            vec![TokenType::Number, TokenType::Number, TokenType::Eof]
+        );
+        // the end of input sits right after the last token, like in rendered diagnostics
+        assert_eq!(scan_res.line_nrs, vec![1, 3, 3]);
+        assert!(scan_res.errors.is_empty());
+    }
+
+    #[test]
+    fn test_unterminated_string_still_ends_in_eof() {
+        let scan_res = Scanner::new("print \"abc").run();
+
+        assert_eq!(scan_res.tags, vec![TokenType::Print, TokenType::Eof]);
+        assert_eq!(scan_res.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_spans() {
+        let code = "\"hello\" >= x";
+        let scan_res = Scanner::new(code).run();
+
+        assert_eq!(
+            scan_res.tags,
+            vec![
+                TokenType::String,
+                TokenType::GreaterEqual,
+                TokenType::Identifier,
+                TokenType::Eof
+            ]
+        );
+        assert_eq!(
+            scan_res.spans,
+            vec![
+                Span::new(0, 7),
+                Span::new(8, 10),
+                Span::new(11, 12),
+                Span::new(12, 12)
+            ]
+        );
+        assert_eq!(
+            &code[scan_res.spans[0].start..scan_res.spans[0].end],
+            "\"hello\""
+        );
+    }
+
+    #[test]
+    fn test_unicode_in_strings_and_comments() {
+        let code = "// emoji 🎉 in a comment\nprint \"héllo\";";
+        let scan_res = Scanner::new(code).run();
+
+        assert!(scan_res.errors.is_empty());
+        assert_eq!(
+            scan_res.tags,
+            vec![
+                TokenType::Print,
+                TokenType::String,
+                TokenType::Semicolon,
+                TokenType::Eof
+            ]
+        );
+        assert_eq!(scan_res.tokens[1], Token::new("héllo", true));
+        let span = scan_res.spans[1];
+        assert_eq!(&code[span.start..span.end], "\"héllo\"");
+        assert_eq!(scan_res.line_nrs[1], 2);
+    }
+
+    #[test]
+    fn test_unexpected_characters() {
+        let scan_res = Scanner::new("var a @= 1;\n#").run();
+
+        let errors: Vec<_> = scan_res.errors.iter().map(|err| err.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "[line: 1 column: 7] Error: Unexpected character '@'.",
+                "[line: 2 column: 1] Error: Unexpected character '#'.",
+            ]
+        );
+        // scanning carries on past them
+        assert_eq!(
+            scan_res.tags,
+            vec![
+                TokenType::Var,
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::Number,
+                TokenType::Semicolon,
+                TokenType::Eof
+            ]
+        );
+
+        let scan_res = Scanner::new("é").run();
+        assert_eq!(scan_res.tags, vec![TokenType::Eof]);
+        assert_eq!(scan_res.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_iterates_lazily() {
+        let mut scanner = Scanner::new("1 @ 2");
+
+        let first = scanner.next().unwrap().unwrap();
+        assert_eq!(first.tag, TokenType::Number);
+        assert_eq!(first.span, Span::new(0, 1));
+        // nothing past the first token has been looked at yet
+        assert!(!scanner.has_errors());
+
+        let err = scanner.next().unwrap().unwrap_err();
+        assert_eq!(err.message, "Unexpected character '@'.");
+        assert_eq!((err.line, err.col), (1, 3));
+        assert_eq!(err.span, Span::new(2, 3));
+
+        let tags: Vec<_> = scanner.by_ref().map(|tok| tok.unwrap().tag).collect();
+        assert_eq!(tags, vec![TokenType::Number, TokenType::Eof]);
+        assert!(scanner.next().is_none());
+        assert!(scanner.has_errors());
+        assert_eq!(scanner.errors(), &[err]);
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let code = "1 /* one\n/* nested\n */ still comment */ 2 /**/ 3";
+        let scan_res = Scanner::new(code).run();
+
         assert!(scan_res.errors.is_empty());
+        assert_eq!(
+            scan_res.tags,
+            vec![
+                TokenType::Number,
+                TokenType::Number,
+                TokenType::Number,
+                TokenType::Eof
+            ]
+        );
+        assert_eq!(scan_res.line_nrs, vec![1, 3, 3, 3]);
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let scan_res = Scanner::new("print 1;\n  /* open /* nested */\n print 2;").run();
+
+        assert_eq!(
+            scan_res.tags,
+            vec![
+                TokenType::Print,
+                TokenType::Number,
+                TokenType::Semicolon,
+                TokenType::Eof
+            ]
+        );
+        let errors: Vec<_> = scan_res.errors.iter().map(|err| err.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec!["[line: 2 column: 3] Error: Unterminated block comment."]
+        );
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let code = r#""tab\tquote\"slash\\\nsmile\u{1F600}\u{e9}""#;
+        let scan_res = Scanner::new(code).run();
+
+        assert!(scan_res.errors.is_empty());
+        assert_eq!(
+            scan_res.tokens[0],
+            Token::new("tab\tquote\"slash\\\nsmile😀é", true)
+        );
+        assert_eq!(scan_res.spans[0], Span::new(0, code.len()));
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        let code = r#""a\qb" "\u{110000}" "\u{zz}" "\u1""#;
+        let scan_res = Scanner::new(code).run();
+
+        let errors: Vec<_> = scan_res.errors.iter().map(|err| err.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                r"[line: 1 column: 3] Error: Invalid escape sequence '\q'.",
+                r"[line: 1 column: 9] Error: Invalid unicode escape '\u{110000}'.",
+                r"[line: 1 column: 22] Error: Expect 1 to 6 hex digits in '\u{...}'.",
+                r"[line: 1 column: 31] Error: Expect '{' after '\u'.",
+            ]
+        );
+        // the strings themselves are still scanned
+        assert_eq!(scan_res.tags.len(), 5);
+        assert_eq!(scan_res.tokens[0], Token::new("aqb", true));
+    }
+
+    #[test]
+    fn test_multi_line_strings() {
+        let code = "\"one\ntwo\nthree\" x\ny";
+        let scan_res = Scanner::new(code).run();
+
+        assert_eq!(scan_res.tokens[0], Token::new("one\ntwo\nthree", true));
+        // a string is on the line it opens on
+        assert_eq!(scan_res.line_nrs, vec![1, 3, 4, 4]);
+        assert_eq!(scan_res.end_cols[1], 7);
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let code = r#""a ${b + "${c}"} d ${ {} } \${e}""#;
+        let scan_res = Scanner::new(code).run();
+
+        assert!(scan_res.errors.is_empty());
+        assert_eq!(
+            scan_res.tags,
+            vec![
+                TokenType::Interpolation,
+                TokenType::Identifier,
+                TokenType::Plus,
+                TokenType::Interpolation,
+                TokenType::Identifier,
+                TokenType::InterpolationEnd,
+                TokenType::Interpolation,
+                TokenType::LeftBrace,
+                TokenType::RightBrace,
+                TokenType::InterpolationEnd,
+                TokenType::Eof
+            ]
+        );
+        assert_eq!(scan_res.tokens[0], Token::new("a ", true));
+        assert_eq!(scan_res.tokens[6], Token::new(" d ", true));
+        assert_eq!(scan_res.tokens[9], Token::new(" ${e}", true));
+        assert_eq!(scan_res.spans[0], Span::new(0, 5));
+    }
+
+    #[test]
+    fn test_number_formats() {
+        let scan_res = Scanner::new("0xFF 0b1010 1e-9 2.5E3 1_000_000 0xdead_BEEF").run();
+
+        assert!(scan_res.errors.is_empty());
+        let values: Vec<_> = scan_res.tokens.iter().map(|tok| &tok.literal).collect();
+        assert_eq!(
+            values,
+            vec![
+                &Literal::Num(255.0),
+                &Literal::Num(10.0),
+                &Literal::Num(1e-9),
+                &Literal::Num(2500.0),
+                &Literal::Num(1_000_000.0),
+                &Literal::Num(f64::from(0xdead_beef_u32)),
+                &Literal::None,
+            ]
+        );
+        // the lexeme is kept as written
+        assert_eq!(&*scan_res.tokens[4].lexeme, "1_000_000");
+    }
+
+    #[test]
+    fn test_malformed_numbers() {
+        let scan_res = Scanner::new("0x 1e 0b102 1__0 12abc 3_").run();
+
+        let errors: Vec<_> = scan_res.errors.iter().map(|err| err.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "[line: 1 column: 1] Error: Expect digits after '0x'.",
+                "[line: 1 column: 4] Error: Expect digits in the exponent of '1e'.",
+                "[line: 1 column: 7] Error: Invalid digit '2' in number literal '0b102'.",
+                "[line: 1 column: 13] Error: '_' must sit between digits in '1__0'.",
+                "[line: 1 column: 18] Error: Invalid digit 'a' in number literal '12abc'.",
+                "[line: 1 column: 24] Error: '_' must sit between digits in '3_'.",
+            ]
+        );
+        // each malformed number is still a single token
+        assert_eq!(scan_res.tags.len(), 7);
+    }
+
+    #[test]
+    fn test_numeric_strings_stay_strings() {
+        let scan_res = Scanner::new(r#""12" 1.2.3"#).run();
+
+        assert_eq!(scan_res.tokens[0].literal, Literal::String(Rc::from("12")));
+        assert_eq!(
+            scan_res.tags,
+            vec![
+                TokenType::String,
+                TokenType::Number,
+                TokenType::Dot,
+                TokenType::Number,
+                TokenType::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dump() {
+        let dump = Scanner::new("var a = \"x\\n\";\nprint 1.5;")
+            .run()
+            .to_string();
+        let expected = [
+            "   1 0..3        Var              var",
+            "   1 4..5        Identifier       a",
+            "   1 6..7        Equal            =",
+            "   1 8..13       String           \\\"x\\n\\\"",
+            "   1 13..14      Semicolon        ;",
+            "   2 15..20      Print            print",
+            "   2 21..24      Number           1.5",
+            "   2 24..25      Semicolon        ;",
+            "   2 25..25      Eof",
+            "",
+        ];
+        assert_eq!(dump, expected.join("\n"));
+    }
+
+    /// More of a benchmark than a test, run it with
+    /// `cargo test --release -- --ignored --nocapture test_scanning_is_linear`
+    #[test]
+    #[ignore]
+    fn test_scanning_is_linear() {
+        let chunk =
+            "var a = 1.5 * (b + \"some string\"); // a comment\nfun f(x) { return x >= 2; }\n";
+        let time = |copies: usize| {
+            let code = chunk.repeat(copies);
+            let start = Instant::now();
+            let scan_res = Scanner::new(&code).run();
+            let elapsed = start.elapsed();
+            assert!(scan_res.errors.is_empty());
+            println!("{:>8} bytes: {elapsed:?}", code.len());
+            elapsed
+        };
+
+        let base_copies = 1_000;
+        let base = time(base_copies);
+        for factor in [2u32, 4, 8, 16] {
+            let elapsed = time(base_copies * factor as usize);
+            // generous for noise, quadratic scanning would be off by `factor` times more
+            assert!(
+                elapsed < base * factor * 3,
+                "{factor}x the code took {elapsed:?}"
+            );
+        }
     }
 }
@@ -1,24 +1,24 @@
+//! The tokens the [scanner](crate::scanner) produces, and where in the source they came from
+
 use std::{fmt::Display, rc::Rc};
 
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) struct Token {
+pub struct Token {
     // pub(crate) tag: TokenType,
-    pub(crate) lexeme: Rc<str>,
-    pub(crate) literal: Value,
+    /// The text of the token, or the value of a string after its escapes were processed
+    pub lexeme: Rc<str>,
+    pub literal: Literal,
     // line: usize,
 }
 
 impl Token {
+    /// A token carrying no literal, or a string literal holding `lexeme` when `literal` is set
     pub(crate) fn new(lexeme: &str, literal: bool) -> Self {
         let rc: Rc<str> = Rc::from(lexeme);
         let literal = if literal {
-            if let Ok(num) = lexeme.parse() {
-                Value::Num(num)
-            } else {
-                Value::String(rc.clone())
-            }
+            Literal::String(rc.clone())
         } else {
-            Value::None
+            Literal::None
         };
         Self {
             // tag,
@@ -26,6 +26,60 @@ impl Token {
             literal,
         }
     }
+
+    /// A number literal, `lexeme` is kept as written in the source
+    pub(crate) fn number(lexeme: &str, value: f64) -> Self {
+        Self {
+            lexeme: Rc::from(lexeme),
+            literal: Literal::Num(value),
+        }
+    }
+}
+
+/// A token together with everything known about where it was found,
+/// as handed out one at a time by the scanner
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub tag: TokenType,
+    pub token: Token,
+    /// Line the token starts on
+    pub line: usize,
+    /// 1-based column the token starts at, counted in chars
+    pub col: usize,
+    /// Column the token ends at
+    pub end_col: usize,
+    pub span: Span,
+}
+
+/// Where a token was found, as recorded in [`crate::scanner::TokenInfo`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Loc {
+    pub line: usize,
+    /// 1-based, counted in chars like the columns of rendered diagnostics
+    pub col: usize,
+    /// The whole token
+    pub span: Span,
+}
+
+/// A byte range `start..end` into the source code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -57,6 +111,10 @@ pub enum TokenType {
     Identifier,
     String,
     Number,
+    /// The part of an interpolated string up to a `${`
+    Interpolation,
+    /// The part of an interpolated string after the last `}`
+    InterpolationEnd,
 
     // Keywords.
     And,
@@ -80,28 +138,6 @@ pub enum TokenType {
 }
 
 impl TokenType {
-    pub(crate) fn is_keyword(&self) -> bool {
-        matches!(
-            self,
-            TokenType::And
-                | TokenType::Class
-                | TokenType::Else
-                | TokenType::False
-                | TokenType::Fun
-                | TokenType::For
-                | TokenType::If
-                | TokenType::Nil
-                | TokenType::Or
-                | TokenType::Print
-                | TokenType::Return
-                | TokenType::Super
-                | TokenType::This
-                | TokenType::True
-                | TokenType::Var
-                | TokenType::While
-        )
-    }
-
     pub(crate) fn from_str(value: &str) -> Option<Self> {
         match value {
             "(" => Some(TokenType::LeftParen),
@@ -188,6 +224,8 @@ impl Display for TokenType {
             TokenType::Identifier => write!(f, "Identifier"),
             TokenType::String => write!(f, "String"),
             TokenType::Number => write!(f, "Number"),
+            TokenType::Interpolation => write!(f, "Interpolation"),
+            TokenType::InterpolationEnd => write!(f, "InterpolationEnd"),
             TokenType::And => write!(f, "And"),
             TokenType::Class => write!(f, "Class"),
             TokenType::Else => write!(f, "Else"),
@@ -209,19 +247,20 @@ impl Display for TokenType {
     }
 }
 
+/// The literal carried by a [`Token`], runtime values are kept by the interpreter
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) enum Value {
+pub enum Literal {
     String(Rc<str>),
     Num(f64),
     None,
 }
 
-impl Display for Value {
+impl Display for Literal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Value::String(s) => write!(f, "\"{s}\""),
-            Value::Num(num) => write!(f, "{num}"),
-            Value::None => write!(f, "nil"),
+            Literal::String(s) => write!(f, "\"{s}\""),
+            Literal::Num(num) => write!(f, "{num}"),
+            Literal::None => write!(f, "nil"),
         }
     }
 }